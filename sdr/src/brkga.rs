@@ -0,0 +1,136 @@
+use std::cmp::Ordering;
+
+use itertools::Itertools;
+use jagua_rs::fsize;
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::sdr_config::BrkgaConfig;
+use crate::sdr_optimizer::PlacementOrder;
+
+/// Fitness of a decoded chromosome, lower is better: the number of copies which did not fit,
+/// then the number of lines of the trimmed strip
+pub type Fitness = (usize, usize);
+
+/// Random keys in [0, 1), the first half orders the items by ascending key,
+/// the second half selects the orientation every item tries first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chromosome {
+    pub keys: Vec<fsize>,
+}
+
+impl Chromosome {
+    pub fn random(n_items: usize, rng: &mut impl Rng) -> Self {
+        Self { keys: (0..2 * n_items).map(|_| rng.random::<fsize>()).collect() }
+    }
+
+    /// Chromosome which decodes to `order`
+    pub fn from_order(order: &PlacementOrder, n_orientations: &[usize]) -> Self {
+        let n_items = order.items.len();
+        let mut keys = vec![0.0; 2 * n_items];
+        for (position, item_id) in order.items.iter().enumerate() {
+            keys[*item_id] = position as fsize / n_items as fsize;
+        }
+        for (item_id, first) in order.first_orientations.iter().enumerate() {
+            let n = n_orientations[item_id].max(1);
+            keys[n_items + item_id] = ((first % n) as fsize + 0.5) / n as fsize;
+        }
+        Self { keys }
+    }
+
+    pub fn decode(&self, n_orientations: &[usize]) -> PlacementOrder {
+        let n_items = n_orientations.len();
+        let (order_keys, orientation_keys) = self.keys.split_at(n_items);
+        let items = (0..n_items)
+            .sorted_by(|a, b| order_keys[*a].partial_cmp(&order_keys[*b]).unwrap_or(Ordering::Equal))
+            .collect_vec();
+        let first_orientations = orientation_keys
+            .iter()
+            .zip(n_orientations)
+            .map(|(key, n)| ((key * *n as fsize) as usize).min(n.saturating_sub(1)))
+            .collect();
+        PlacementOrder { items, first_orientations }
+    }
+
+    /// Every key is inherited from the elite parent with probability `elite_bias`
+    fn crossover(elite: &Chromosome, other: &Chromosome, elite_bias: f32, rng: &mut impl Rng) -> Self {
+        let keys = elite
+            .keys
+            .iter()
+            .zip(&other.keys)
+            .map(|(e, o)| if rng.random::<f32>() < elite_bias { *e } else { *o })
+            .collect();
+        Self { keys }
+    }
+}
+
+/// Population of a biased random-key genetic algorithm, sorted from best to worst.
+/// Every generation keeps the elites, adds fresh random mutants and fills the rest with the offspring of an elite
+/// and a non-elite parent. The chromosomes of a generation are decoded and evaluated in parallel.
+pub struct Brkga {
+    config: BrkgaConfig,
+    n_orientations: Vec<usize>,
+    population: Vec<(Chromosome, Fitness)>,
+}
+
+impl Brkga {
+    /// Evaluates an initial population made of `seed` and random chromosomes
+    pub fn new(
+        config: BrkgaConfig,
+        n_orientations: Vec<usize>,
+        seed: &PlacementOrder,
+        rng: &mut impl Rng,
+        evaluate: impl Fn(&PlacementOrder) -> Fitness + Sync,
+    ) -> Self {
+        let n_items = n_orientations.len();
+        let initial = std::iter::once(Chromosome::from_order(seed, &n_orientations))
+            .chain((1..config.population_size.max(2)).map(|_| Chromosome::random(n_items, rng)))
+            .collect_vec();
+        let mut brkga = Self { config, n_orientations, population: vec![] };
+        brkga.population = brkga.evaluate_all(initial, &evaluate);
+        brkga.population.sort_by_key(|(_, fitness)| *fitness);
+        brkga
+    }
+
+    /// Evolves the population by one generation, returns whether the best chromosome improved
+    pub fn step(&mut self, rng: &mut impl Rng, evaluate: impl Fn(&PlacementOrder) -> Fitness + Sync) -> bool {
+        let population_size = self.population.len();
+        let n_elites = ((population_size as f32 * self.config.elite_frac).round() as usize).clamp(1, population_size - 1);
+        let n_mutants = ((population_size as f32 * self.config.mutant_frac).round() as usize).min(population_size - n_elites);
+        let n_items = self.n_orientations.len();
+        let best_fitness = self.best().1;
+
+        let (elites, others) = self.population.split_at(n_elites);
+        let mut next = (0..n_mutants).map(|_| Chromosome::random(n_items, rng)).collect_vec();
+        while next.len() < population_size - n_elites {
+            let elite = &elites[rng.random_range(0..elites.len())].0;
+            let other = match others.is_empty() {
+                true => &elites[rng.random_range(0..elites.len())].0,
+                false => &others[rng.random_range(0..others.len())].0,
+            };
+            next.push(Chromosome::crossover(elite, other, self.config.elite_bias, rng));
+        }
+        let mut next_population = self.population[..n_elites].to_vec();
+        next_population.extend(self.evaluate_all(next, &evaluate));
+        // the sort is stable, an elite is only replaced by a strictly better chromosome
+        next_population.sort_by_key(|(_, fitness)| *fitness);
+        self.population = next_population;
+        self.best().1 < best_fitness
+    }
+
+    /// Decoded order and fitness of the best chromosome
+    pub fn best(&self) -> (PlacementOrder, Fitness) {
+        let (chromosome, fitness) = &self.population[0];
+        (chromosome.decode(&self.n_orientations), *fitness)
+    }
+
+    fn evaluate_all(&self, chromosomes: Vec<Chromosome>, evaluate: &(impl Fn(&PlacementOrder) -> Fitness + Sync)) -> Vec<(Chromosome, Fitness)> {
+        chromosomes
+            .into_par_iter()
+            .map(|chromosome| {
+                let fitness = evaluate(&chromosome.decode(&self.n_orientations));
+                (chromosome, fitness)
+            })
+            .collect()
+    }
+}
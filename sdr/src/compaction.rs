@@ -0,0 +1,106 @@
+use itertools::Itertools;
+use jagua_rs::fsize;
+use jagua_rs::geometry::d_transformation::DTransformation;
+use ordered_float::OrderedFloat;
+
+use crate::validation::ExactLayout;
+
+/// Maximum number of bisections to find how far an item slides once it hit a hazard
+const MAX_BISECTIONS: usize = 20;
+
+/// Direction in which the items of a layout are slid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlideDirection {
+    /// Towards the start of the strip
+    Left,
+    /// Towards the largest y, the side of the strip which the discrete placer fills first
+    Down,
+}
+
+impl SlideDirection {
+    fn vector(self) -> (fsize, fsize) {
+        match self {
+            SlideDirection::Left => (-1.0, 0.0),
+            SlideDirection::Down => (0.0, 1.0),
+        }
+    }
+}
+
+/// Slides every item of the layout left and then down as far as the true geometry allows, `sweeps` times at most.
+/// The items which lie furthest in the direction are slid first. Every slide advances by `step` until the item
+/// collides and then bisects the last step, so an item does not jump over a hazard narrower than `step`.
+/// Returns the total distance over which the items were slid.
+pub fn compact(layout: &mut ExactLayout, sweeps: usize, step: fsize, tolerance: fsize) -> fsize {
+    let mut total = 0.0;
+    for sweep in 0..sweeps {
+        let moved = [SlideDirection::Left, SlideDirection::Down]
+            .into_iter()
+            .map(|direction| slide_all(layout, direction, step, tolerance))
+            .sum::<fsize>();
+        log::debug!("[COMPACT] sweep {} slid the items over {:.3}", sweep, moved);
+        total += moved;
+        if moved <= tolerance {
+            break;
+        }
+    }
+    total
+}
+
+fn slide_all(layout: &mut ExactLayout, direction: SlideDirection, step: fsize, tolerance: fsize) -> fsize {
+    let order = (0..layout.items.len())
+        .sorted_by_key(|index| {
+            let bbox = layout.item_bbox(*index);
+            match direction {
+                SlideDirection::Left => OrderedFloat(bbox.x_min),
+                SlideDirection::Down => OrderedFloat(-bbox.y_max),
+            }
+        })
+        .collect_vec();
+    order.into_iter().map(|index| slide(layout, index, direction, step, tolerance)).sum()
+}
+
+/// Slides one item as far as it goes without colliding, returns the distance
+fn slide(layout: &mut ExactLayout, index: usize, direction: SlideDirection, step: fsize, tolerance: fsize) -> fsize {
+    let bbox = layout.item_bbox(index);
+    let bin_bbox = layout.bin_bbox();
+    let max_distance = match direction {
+        SlideDirection::Left => bbox.x_min - bin_bbox.x_min,
+        SlideDirection::Down => bin_bbox.y_max - bbox.y_max,
+    };
+    if max_distance <= tolerance {
+        return 0.0;
+    }
+    let d_transf = layout.items[index].d_transf;
+    let (x, y) = d_transf.translation();
+    let (dx, dy) = direction.vector();
+    let slid = |distance: fsize| DTransformation::new(d_transf.rotation(), (x + dx * distance, y + dy * distance));
+
+    let mut feasible = 0.0;
+    let mut infeasible = None;
+    while feasible < max_distance {
+        let distance = (feasible + step).min(max_distance);
+        if layout.collides(index, &slid(distance)) {
+            infeasible = Some(distance);
+            break;
+        }
+        feasible = distance;
+    }
+    if let Some(mut infeasible) = infeasible {
+        for _ in 0..MAX_BISECTIONS {
+            if infeasible - feasible <= tolerance {
+                break;
+            }
+            let distance = (feasible + infeasible) / 2.0;
+            match layout.collides(index, &slid(distance)) {
+                true => infeasible = distance,
+                false => feasible = distance,
+            }
+        }
+    }
+    if feasible > tolerance {
+        layout.move_item(index, slid(feasible));
+        feasible
+    } else {
+        0.0
+    }
+}
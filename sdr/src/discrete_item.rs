@@ -1,3 +1,4 @@
+use itertools::Itertools;
 use jagua_rs::fsize;
 use jagua_rs::geometry::primitives::edge::Edge;
 use jagua_rs::geometry::primitives::simple_polygon::SimplePolygon;
@@ -5,27 +6,91 @@ use jagua_rs::{entities::item::Item, geometry::{geo_traits::Transformable, primi
 
 use crate::discrete_line::{DiscreteLine, Interval, EPSILON};
 
+/// Deviation of the points of two congruent orientations, relative to the size of their shapes,
+/// the rotations of the shapes are only exact up to rounding
+const CONGRUENCE_TOLERANCE: fsize = 1e-5;
+
+/// Geometry of an item which cannot be represented by the single `SimplePolygon` of a jagua `Item`
+#[derive(Debug, Clone, Default)]
+pub struct ItemGeometry {
+    /// Holes in the shape, in the same reference frame as the shape of the item
+    pub holes: Vec<SimplePolygon>,
+    /// Disjoint components of an item made of several polygons, its shape is then their convex hull
+    pub components: Vec<ItemComponent>,
+}
+
+/// One of the polygons of an item made of several disjoint polygons, or an obstacle on a strip
+#[derive(Debug, Clone)]
+pub struct ItemComponent {
+    pub outer: SimplePolygon,
+    pub holes: Vec<SimplePolygon>,
+}
+
+impl ItemGeometry {
+    pub fn transform_clone(&self, t: &Transformation) -> ItemGeometry {
+        ItemGeometry {
+            holes: self.holes.iter().map(|hole| hole.transform_clone(t)).collect(),
+            components: self.components.iter().map(|component| component.transform_clone(t)).collect(),
+        }
+    }
+
+    /// Mirror image of the geometry, see `mirror_polygon`
+    pub fn mirror_clone(&self) -> ItemGeometry {
+        ItemGeometry {
+            holes: self.holes.iter().map(mirror_polygon).collect(),
+            components: self.components.iter().map(|component| component.mirror_clone()).collect(),
+        }
+    }
+}
+
+impl ItemComponent {
+    /// Discretizes the component where it lies: line `i` is at x = `i * resolution`
+    /// and the intervals hold the distance below y = 0.
+    pub fn discretize(&self, resolution: fsize) -> Vec<DiscreteLine> {
+        let outer = &self.outer;
+        let mut lines = outer.sweep_line_algorithm(resolution, outer);
+        // Free the cross-sections of the holes before extending, so the extensions are not carved away
+        for hole in &self.holes {
+            outer.carve_hole(&mut lines, hole, resolution);
+        }
+        outer.apply_extension(&mut lines, outer, resolution);
+        for hole in &self.holes {
+            outer.apply_hole_extension(&mut lines, hole, resolution);
+        }
+        lines
+    }
+
+    pub fn transform_clone(&self, t: &Transformation) -> ItemComponent {
+        ItemComponent {
+            outer: self.outer.transform_clone(t),
+            holes: self.holes.iter().map(|hole| hole.transform_clone(t)).collect(),
+        }
+    }
+
+    pub fn mirror_clone(&self) -> ItemComponent {
+        ItemComponent {
+            outer: mirror_polygon(&self.outer),
+            holes: self.holes.iter().map(mirror_polygon).collect(),
+        }
+    }
+}
+
 // Define a trait to be implemented for Item
 pub trait Discretizable {
-    fn cross_product(&self, o: &Point, a: &Point, b: &Point) -> fsize;
-    fn discretize_shape(&self, resolution: fsize, rotation: fsize) -> Vec<DiscreteLine>;
-    fn sweep_line_algorithm(&self, resolution: fsize, first_quadrant: &SimplePolygon) -> Vec<DiscreteLine>;
-    fn convex_vertex(&self) -> Vec<usize>;
-    fn move_to_first_quadrant(&self, rotation: fsize) -> Transformation;
-    fn process_edge_extensions(&self, discretized_lines: &mut Vec<DiscreteLine>, edge: &Edge, 
-        vertex: &Point, left_line_idx: usize, right_line_idx: usize,
-        left_line_x: fsize, right_line_x: fsize);
-    fn extend_convex_vertex(&self, discretized_lines: &mut Vec<DiscreteLine>, vertex: &Point, v_index: usize, first_quadrant: &SimplePolygon, resolution: fsize);
-    fn add_extension_interval(&self, line_idx: usize, interval: Interval, discretized_lines: &mut Vec<DiscreteLine>);
-    fn apply_extension(&self, discretized_lines: &mut Vec<DiscreteLine>, first_quadrant: &SimplePolygon, resolution: fsize);
-}
+    /// Polygon to discretize
+    fn shape(&self) -> &SimplePolygon;
 
-// Implement the trait for Item
-impl Discretizable for Item {
     fn sweep_line_algorithm(&self, resolution: fsize, first_quadrant: &SimplePolygon) -> Vec<DiscreteLine> {
         let rect = &first_quadrant.bbox;
-        let mut results = Vec::new();
-        let mut x_line = rect.x_min;
+        // Lines lie on the grid of the resolution, so the components of an item line up
+        let snap_tolerance = resolution * 1e-3;
+        let first_line = ((rect.x_min + snap_tolerance) / resolution).floor().max(0.0) as usize;
+        let grid_x = first_line as fsize * resolution;
+        let mut results = vec![DiscreteLine::new(); first_line];
+        let mut x_line = match (rect.x_min - grid_x).abs() <= snap_tolerance {
+            true => rect.x_min,
+            false => grid_x,
+        };
         
         while x_line <= rect.x_max + EPSILON + resolution{
             let mut last_intersection: Option<fsize> = None;
@@ -40,15 +105,13 @@ impl Discretizable for Item {
                     (edge.end.0, edge.start.0)
                 };
 
-                if x_line >= rect.x_max + EPSILON && x_high >= rect.x_max - EPSILON{
-                    if (edge.start.0 - edge.end.0).abs() < EPSILON {
-                        let mod_val = edge.start.0 % resolution;
-                        let is_near_grid = mod_val < EPSILON || (resolution - mod_val) < EPSILON;
-                        
-                        if !is_near_grid {
-                            merged_intersections.push(Interval::new_with_orientation(edge.end.1.abs(), edge.start.1.abs(), false));
-                            continue;
-                        }
+                if x_line >= rect.x_max + EPSILON && x_high >= rect.x_max - EPSILON && (edge.start.0 - edge.end.0).abs() < EPSILON {
+                    let mod_val = edge.start.0 % resolution;
+                    let is_near_grid = mod_val < EPSILON || (resolution - mod_val) < EPSILON;
+                    
+                    if !is_near_grid {
+                        merged_intersections.push(Interval::new_with_orientation(edge.end.1.abs(), edge.start.1.abs(), false));
+                        continue;
                     }
                 }
 
@@ -83,7 +146,7 @@ impl Discretizable for Item {
                         for e in new_edges{
                             let t = (x_line - e.start.0) / (e.end.0 - e.start.0);
                             let y_intersect = e.start.1 + t * (e.end.1 - e.start.1);
-                            if t >= 0.0 && t <= 1.0{
+                            if (0.0..=1.0).contains(&t){
                                 if y_intersect.abs() > merged_intersections.last().unwrap().end {
                                     merged_intersections.push(Interval::new(merged_intersections.last().unwrap().end, y_intersect.abs()));
                                 }
@@ -100,13 +163,8 @@ impl Discretizable for Item {
                 for edge in edges.iter(){
                     let t = (x_line - edge.start.0) / (edge.end.0 - edge.start.0);
                     let y_intersect = edge.start.1 + t * (edge.end.1 - edge.start.1);
-                    if t >= 0.0 && t <= 1.0{
-                        if edge.end.1 > edge.start.1{
-                            orientation = false;
-                        }
-                        else{
-                            orientation = true;
-                        }
+                    if (0.0..=1.0).contains(&t){
+                        orientation = edge.end.1 <= edge.start.1;
                         if let Some(last_y_val) = last_intersection {
                             if last_y_val != y_intersect {
                                 if last_y_val < y_intersect{
@@ -130,7 +188,10 @@ impl Discretizable for Item {
             if !merged_intersections.is_empty() {
                 let cleaned_intersections = clean_intersections(merged_intersections);
     
-                results.push(DiscreteLine {occupied: cleaned_intersections });
+                results.push(DiscreteLine {occupied: cleaned_intersections, quality_zones: Vec::new() });
+            } else if x_line <= rect.x_max {
+                // keep the indices aligned, extensions can still end up on this line
+                results.push(DiscreteLine::new());
                 // for intersection in cleaned_intersections.into_iter() {
                 //     results.push(DiscreteLine{id: line_counter, occupied: vec![intersection]});
                 // }
@@ -152,32 +213,34 @@ impl Discretizable for Item {
     }
     fn convex_vertex(&self) -> Vec<usize> {
         let mut vertices = Vec::new();
-        let mut i = 0;
-        for vertex in &self.shape.points{
-            let prev = if i == 0 { &self.shape.number_of_points() - 1 } else { i - 1 };
-            let next = if i == &self.shape.number_of_points() - 1 { 0 } else { i + 1 };
-            let cross = self.cross_product(&self.shape.get_point(prev), vertex, &self.shape.get_point(next));
+        for (i, vertex) in self.shape().points.iter().enumerate(){
+            let prev = if i == 0 { &self.shape().number_of_points() - 1 } else { i - 1 };
+            let next = if i == &self.shape().number_of_points() - 1 { 0 } else { i + 1 };
+            let cross = self.cross_product(&self.shape().get_point(prev), vertex, &self.shape().get_point(next));
             if cross > 0.0{
                 vertices.push(i);
             }
-            i += 1;
         }
         // log::info!("Convex Vertices {:?}", vertices);
         vertices
     }
+    /// Places the shape, rotated by `rotation`, against the y axis with its top on the x axis,
+    /// the lines of the sweep measure their intervals downwards from there.
+    /// A rotated shape is anchored on the top of its own bounding box. Anchoring it on its bottom
+    /// shifted by the height of the unrotated shape only holds for rotations keeping that height,
+    /// such as a half turn, any other rotation left the shape above or below the x axis.
     fn move_to_first_quadrant(&self, rotation: fsize) -> Transformation {
         let rotate = Transformation::from_rotation(rotation);
         if rotation != 0.0 {
-            let rotated_shape = self.shape.transform_clone(&rotate);
+            let rotated_shape = self.shape().transform_clone(&rotate);
             let min_x = rotated_shape.points.iter().map(|p| p.0).fold(fsize::MAX, fsize::min);
-            let min_y = rotated_shape.points.iter().map(|p| p.1).fold(fsize::MAX, fsize::min);
-            return rotate.translate((-min_x, -min_y - self.shape.bbox.y_max + self.shape.bbox.y_min));
+            let max_y = rotated_shape.points.iter().map(|p| p.1).fold(fsize::MIN, fsize::max);
+            rotate.translate((-min_x, -max_y))
         }
         else{
-            let min_x = self.shape.points.iter().map(|p| p.0).fold(fsize::MAX, fsize::min);
-            let min_y = self.shape.points.iter().map(|p| p.1).fold(fsize::MAX, fsize::min);
-            let translation = Transformation::from_translation((-min_x, -min_y - self.shape.bbox.y_max + self.shape.bbox.y_min));
-            return translation;
+            let min_x = self.shape().points.iter().map(|p| p.0).fold(fsize::MAX, fsize::min);
+            let min_y = self.shape().points.iter().map(|p| p.1).fold(fsize::MAX, fsize::min);
+            Transformation::from_translation((-min_x, -min_y - self.shape().bbox.y_max + self.shape().bbox.y_min))
         }
     }
     fn extend_convex_vertex(&self, discretized_lines: &mut Vec<DiscreteLine>, vertex: &Point, v_index: usize, 
@@ -226,6 +289,7 @@ impl Discretizable for Item {
 
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_edge_extensions(&self, discretized_lines: &mut Vec<DiscreteLine>, edge: &Edge, 
         vertex: &Point, left_line_idx: usize, right_line_idx: usize,
         left_line_x: fsize, right_line_x: fsize) {
@@ -236,7 +300,7 @@ impl Discretizable for Item {
             // Calculate intersection point
             let t = (left_line_x - edge.start.0) / (edge.end.0 - edge.start.0);
             let y_intersect = edge.start.1 + t * (edge.end.1 - edge.start.1);
-            if t >= 0.0 - EPSILON && t <= 1.0 + EPSILON {
+            if (-EPSILON..=1.0 + EPSILON).contains(&t) {
                 // Add extension interval on left line: (y_A, y_v, R)
                 let left_interval = Interval::new_with_orientation(
                 y_intersect.abs().min(vertex.1.abs()),
@@ -246,7 +310,7 @@ impl Discretizable for Item {
                 self.add_extension_interval(left_line_idx, left_interval, discretized_lines);
                 
                 // if right_line_idx == discretized_lines.last().unwrap().id {
-                //     println!("pointers {:?} {:?}, {:?}", right_line_idx, right_line_x, self.shape.bbox.x_max);
+                //     println!("pointers {:?} {:?}, {:?}", right_line_idx, right_line_x, self.shape().bbox.x_max);
                 //     let right_interval = Interval::new_with_orientation(
                 //         vertex.1.abs(),
                 //         vertex.1.abs(),
@@ -263,7 +327,7 @@ impl Discretizable for Item {
             let t = (right_line_x - edge.start.0) / (edge.end.0 - edge.start.0);
             let y_intersect = edge.start.1 + t * (edge.end.1 - edge.start.1);
 
-            if t >= 0.0 - EPSILON && t <= 1.0 + EPSILON {
+            if (-EPSILON..=1.0 + EPSILON).contains(&t) {
                 // Add extension interval on right line: (y_A, y_v, L)
                 let right_interval = Interval::new_with_orientation(
                 y_intersect.abs().min(vertex.1.abs()),
@@ -307,7 +371,7 @@ impl Discretizable for Item {
     fn add_extension_interval(&self, line_idx: usize, interval: Interval, discretized_lines: &mut Vec<DiscreteLine>) {
         
         if line_idx >= discretized_lines.len() {
-            discretized_lines.push(DiscreteLine { occupied: vec![interval]});
+            discretized_lines.push(DiscreteLine { occupied: vec![interval], quality_zones: Vec::new() });
             return;
         }
         let existing_intervals = &discretized_lines[line_idx].occupied;
@@ -365,8 +429,8 @@ impl Discretizable for Item {
     }
 
     fn apply_extension(&self, discretized_lines: &mut Vec<DiscreteLine>, first_quadrant: &SimplePolygon, resolution: fsize) {
-        // Get convex vertices
-        let convex_indices: Vec<usize> = self.convex_vertex();
+        // Get convex vertices, of the polygon itself as it can also be a component of the item
+        let convex_indices: Vec<usize> = turning_vertices(first_quadrant, true);
         // Process each convex vertex for extension
         for &v_index in &convex_indices {
             let vertex = first_quadrant.get_point(v_index);
@@ -374,11 +438,55 @@ impl Discretizable for Item {
         }
     }
 
+    fn carve_hole(&self, discretized_lines: &mut [DiscreteLine], hole: &SimplePolygon, resolution: fsize) {
+        let first_line = (hole.bbox.x_min / resolution).floor() as usize;
+        let last_line = (hole.bbox.x_max / resolution).ceil() as usize;
+        for (line_idx, line) in discretized_lines.iter_mut().enumerate().take(last_line + 1).skip(first_line) {
+            let free = hole_cross_section(hole, line_idx as fsize * resolution);
+            if !free.is_empty() {
+                line.occupied = subtract_intervals(&line.occupied, &free);
+            }
+        }
+    }
+
+    fn apply_hole_extension(&self, discretized_lines: &mut Vec<DiscreteLine>, hole: &SimplePolygon, resolution: fsize) {
+        // Reflex vertices of the hole are convex vertices of the material sticking into the hole
+        for v_index in turning_vertices(hole, false) {
+            let vertex = hole.get_point(v_index);
+            self.extend_convex_vertex(discretized_lines, &vertex, v_index, hole, resolution);
+        }
+    }
+
     fn discretize_shape(&self, resolution: fsize, rotation: fsize) -> Vec<DiscreteLine> {
+        self.discretize_geometry(resolution, rotation, &ItemGeometry::default())
+    }
+
+    fn discretize_geometry(&self, resolution: fsize, rotation: fsize, geometry: &ItemGeometry) -> Vec<DiscreteLine> {
         //let discretizationtime = Instant::now();
-        let first_quadrant = self.shape.transform_clone(&self.move_to_first_quadrant(rotation));
-        let mut results = self.sweep_line_algorithm(resolution, &first_quadrant); 
-        self.apply_extension(&mut results, &first_quadrant, resolution);
+        let to_first_quadrant = self.move_to_first_quadrant(rotation);
+        let geometry = geometry.transform_clone(&to_first_quadrant);
+        let components = match geometry.components.is_empty() {
+            true => vec![ItemComponent { outer: self.shape().transform_clone(&to_first_quadrant), holes: geometry.holes }],
+            false => geometry.components,
+        };
+
+        let mut results: Vec<DiscreteLine> = Vec::new();
+        for component in &components {
+            let lines = component.discretize(resolution);
+
+            // The lines of all components are on the same grid, merge them
+            if results.len() < lines.len() {
+                results.resize(lines.len(), DiscreteLine::new());
+            }
+            for (line, component_line) in results.iter_mut().zip(lines) {
+                line.occupied.extend(component_line.occupied);
+            }
+        }
+        if components.len() > 1 {
+            for line in results.iter_mut() {
+                line.occupied.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
+            }
+        }
         //log::info!("Time to discretize item processing time: {:?}, rotation: {:?}", discretizationtime.elapsed(), rotation);
 
         results
@@ -386,6 +494,101 @@ impl Discretizable for Item {
     
 }
 
+// Implement the trait for Item
+impl Discretizable for Item {
+    fn shape(&self) -> &SimplePolygon {
+        &self.shape
+    }
+}
+
+// Polygons which are not items, e.g. defects of the strip or the exterior of a bin
+impl Discretizable for SimplePolygon {
+    fn shape(&self) -> &SimplePolygon {
+        self
+    }
+}
+
+/// Reflects a polygon in the y axis, x becomes -x.
+/// The points are reversed to keep their orientation.
+pub fn mirror_polygon(polygon: &SimplePolygon) -> SimplePolygon {
+    SimplePolygon::new(polygon.points.iter().rev().map(|p| Point(-p.0, p.1)).collect())
+}
+
+/// Whether `shape` with `geometry` coincides with `other_shape` with `other_geometry` after a translation,
+/// up to `CONGRUENCE_TOLERANCE` of the size of the shapes. Both are then discretized into the same lines.
+pub fn congruent(shape: &SimplePolygon, geometry: &ItemGeometry, other_shape: &SimplePolygon, other_geometry: &ItemGeometry) -> bool {
+    let (bbox, other_bbox) = (&shape.bbox, &other_shape.bbox);
+    let tolerance = CONGRUENCE_TOLERANCE * bbox.width().max(bbox.height());
+    if (bbox.width() - other_bbox.width()).abs() > tolerance || (bbox.height() - other_bbox.height()).abs() > tolerance {
+        return false;
+    }
+    let offset = Point(other_bbox.x_min - bbox.x_min, other_bbox.y_min - bbox.y_min);
+    let same = |polygon: &SimplePolygon, other: &SimplePolygon| translated_polygon(polygon, other, offset, tolerance);
+    same(shape, other_shape)
+        && matched(&geometry.holes, &other_geometry.holes, same)
+        && matched(&geometry.components, &other_geometry.components, |component, other| {
+            same(&component.outer, &other.outer) && matched(&component.holes, &other.holes, same)
+        })
+}
+
+/// Whether `other` holds the points of `polygon` shifted by `offset`, starting from any of them
+fn translated_polygon(polygon: &SimplePolygon, other: &SimplePolygon, offset: Point, tolerance: fsize) -> bool {
+    let n = polygon.points.len();
+    let shifted = |i: usize, start: usize| {
+        let (p, q) = (polygon.points[i], other.points[(start + i) % n]);
+        (p.0 + offset.0 - q.0).abs() <= tolerance && (p.1 + offset.1 - q.1).abs() <= tolerance
+    };
+    n == other.points.len() && (0..n).any(|start| (0..n).all(|i| shifted(i, start)))
+}
+
+/// Whether every element of `elements` matches another element of `others`
+fn matched<T>(elements: &[T], others: &[T], same: impl Fn(&T, &T) -> bool) -> bool {
+    let mut unmatched = (0..others.len()).collect_vec();
+    elements.len() == others.len()
+        && elements.iter().all(|element| match unmatched.iter().position(|j| same(element, &others[*j])) {
+            Some(position) => {
+                unmatched.swap_remove(position);
+                true
+            }
+            None => false,
+        })
+}
+
+/// Lines which hold the discretized shape inflated by a square of half side `half_width`, so two items whose
+/// dilated lines do not overlap lie at least twice `half_width` apart. The lines are copied onto the
+/// `spacing_lines` lines on either side of them and their intervals are stretched by `half_width` on either end.
+/// The shape starts `spacing_lines` lines further and `half_width` lower than in `lines`.
+pub fn dilate_lines(lines: &[DiscreteLine], half_width: fsize, resolution: fsize) -> Vec<DiscreteLine> {
+    let k = spacing_lines(half_width, resolution);
+    (0..lines.len() + 2 * k)
+        .map(|i| {
+            let mut stretched = lines[i.saturating_sub(2 * k)..(i + 1).min(lines.len())]
+                .iter()
+                .flat_map(|line| &line.occupied)
+                .map(|interval| (interval.start, interval.end + 2.0 * half_width))
+                .collect_vec();
+            stretched.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            // the orientations are dropped, dilated shapes cannot touch anyway
+            let mut merged: Vec<(fsize, fsize)> = Vec::with_capacity(stretched.len());
+            for (start, end) in stretched {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            DiscreteLine {
+                occupied: merged.into_iter().map(|(start, end)| Interval::new(start, end)).collect(),
+                quality_zones: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Lines added on either side of the lines of an item by `dilate_lines`, enough to cover `half_width`
+pub fn spacing_lines(half_width: fsize, resolution: fsize) -> usize {
+    (half_width / resolution - EPSILON).ceil().max(0.0) as usize
+}
+
 fn approx_eq(p1: &Point, p2: &Point) -> bool {
     (p1.0 - p2.0).abs() < EPSILON && (p1.1 - p2.1).abs() < EPSILON
 }
@@ -400,7 +603,7 @@ fn clean_intersections(intersections: Vec<Interval>) -> Vec<Interval> {
     sorted_intersections.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
     
     let mut result = Vec::new();
-    let mut current = sorted_intersections[0].clone();
+    let mut current = sorted_intersections[0];
     
     for next in sorted_intersections.into_iter().skip(1) {
         // Check if the next intersection overlaps or is adjacent to the current one
@@ -430,3 +633,85 @@ fn clean_intersections(intersections: Vec<Interval>) -> Vec<Interval> {
     result
 }
 
+/// Indices of the convex (or reflex when `convex` is false) vertices of a counterclockwise polygon
+fn turning_vertices(polygon: &SimplePolygon, convex: bool) -> Vec<usize> {
+    let n_points = polygon.number_of_points();
+    (0..n_points)
+        .filter(|&i| {
+            let Point(ox, oy) = polygon.get_point((i + n_points - 1) % n_points);
+            let Point(ax, ay) = polygon.get_point(i);
+            let Point(bx, by) = polygon.get_point((i + 1) % n_points);
+            let cross = (ax - ox) * (by - oy) - (ay - oy) * (bx - ox);
+            match convex {
+                true => cross > 0.0,
+                false => cross < 0.0,
+            }
+        })
+        .collect()
+}
+
+/// Free space of a hole on the line at `x_line`, as (start, end) pairs in the same coordinates as the intervals.
+/// A line on a vertical edge of the hole is free up to that edge, so items can touch it.
+fn hole_cross_section(hole: &SimplePolygon, x_line: fsize) -> Vec<(fsize, fsize)> {
+    let mut free = [true, false]
+        .into_iter()
+        .flat_map(|closed_left| {
+            let ys = hole.edge_iter()
+                .filter(|edge| {
+                    let (x_low, x_high) = (edge.start.0.min(edge.end.0), edge.start.0.max(edge.end.0));
+                    match closed_left {
+                        true => x_low <= x_line && x_line < x_high,
+                        false => x_low < x_line && x_line <= x_high,
+                    }
+                })
+                .map(|edge| {
+                    let t = (x_line - edge.start.0) / (edge.end.0 - edge.start.0);
+                    (edge.start.1 + t * (edge.end.1 - edge.start.1)).abs()
+                })
+                .sorted_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .collect_vec();
+            ys.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect_vec()
+        })
+        .sorted_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .collect_vec();
+
+    // Merge the cross-sections of both sides of the line
+    free.dedup_by(|next, current| {
+        if next.0 <= current.1 {
+            current.1 = current.1.max(next.1);
+            true
+        } else {
+            false
+        }
+    });
+    free
+}
+
+/// Removes the free ranges from the occupied intervals, keeping the orientation of what remains
+fn subtract_intervals(occupied: &[Interval], free: &[(fsize, fsize)]) -> Vec<Interval> {
+    occupied
+        .iter()
+        .flat_map(|interval| {
+            let mut remaining = vec![(interval.start, interval.end)];
+            for &(free_start, free_end) in free {
+                remaining = remaining
+                    .into_iter()
+                    .flat_map(|(start, end)| {
+                        if end <= free_start || start >= free_end {
+                            vec![(start, end)]
+                        } else {
+                            [(start, free_start), (free_end, end)]
+                                .into_iter()
+                                .filter(|(s, e)| e - s > EPSILON)
+                                .collect_vec()
+                        }
+                    })
+                    .collect();
+            }
+            remaining.into_iter().map(move |(start, end)| match interval.orientation {
+                Some(orientation) => Interval::new_with_orientation(start, end, orientation),
+                None => Interval::new(start, end),
+            })
+        })
+        .collect()
+}
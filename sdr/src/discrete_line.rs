@@ -1,22 +1,21 @@
 // use std::arch::x86_64::__m512;
 // // use std::simd::{f32x16, mask32x16, Simd};
-use rayon::prelude::*;
+use std::cell::Cell;
+use std::time::Duration;
+use jagua_rs::{fsize, geometry::transformation::Transformation};
 
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Instant;
+use jagua_rs::entities::quality_zone::InferiorQualityZone;
+use jagua_rs::geometry::primitives::aa_rectangle::AARectangle;
 
-use jagua_rs::{entities::item, fsize, geometry::transformation::Transformation};
+use crate::discrete_item::ItemComponent;
 use ordered_float::Float;
+use serde::{Deserialize, Serialize};
 pub const EPSILON:fsize = 0.000001;
 
-static OVERLAPS_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Stands in for the lines a strip can still grow
+static EMPTY_LINE: DiscreteLine = DiscreteLine { occupied: Vec::new(), quality_zones: Vec::new() };
 
-// Function to get the current count
-pub fn get_overlaps_count() -> usize {
-    OVERLAPS_COUNTER.load(Ordering::Relaxed)
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Interval{
     pub start: fsize,
     pub end: fsize,
@@ -24,9 +23,11 @@ pub struct Interval{
     total_space: fsize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscreteLine{
     pub occupied: Vec<Interval>,
+    /// Spans of the line lying in inferior quality zones, with the quality of the zone
+    pub quality_zones: Vec<(usize, Interval)>,
 }
 
 #[derive(Debug, Clone)]
@@ -38,7 +39,54 @@ pub struct DiscreteObject{
 
 #[derive(Debug, Clone)]
 pub struct DiscreteStrip{
-    pub lines: Vec<DiscreteLine>
+    pub lines: Vec<DiscreteLine>,
+    /// Empty lines are appended on demand when an item is placed past the end, up to this many lines
+    pub max_lines: usize,
+    /// Lines up to the last one holding an item, the obstacles are not counted
+    pub item_lines: usize,
+    /// Work done on the strip so far
+    pub counters: StripCounters,
+}
+
+/// Work done on a `DiscreteStrip`, the cells are counted through shared references
+#[derive(Debug, Clone, Default)]
+pub struct StripCounters {
+    /// Overlap checks between two intervals
+    pub overlap_checks: Cell<usize>,
+    /// Lines on which the placement of an item was tried
+    pub lines_scanned: Cell<usize>,
+    /// Time spent placing every item on the strip, per item id
+    pub item_times: Vec<Duration>,
+}
+
+impl StripCounters {
+    pub fn add_item_time(&mut self, item_id: usize, time: Duration) {
+        if self.item_times.len() <= item_id {
+            self.item_times.resize(item_id + 1, Duration::ZERO);
+        }
+        self.item_times[item_id] += time;
+    }
+}
+
+/// Discretizes a component on the lines of `frame`, keeping only the spans between its top and its bottom.
+/// The sweep measures the edges by their distance to the top, so the component is moved below the top first,
+/// otherwise the part of it above the frame would be folded back onto the lines.
+fn discretize_in_frame(component: &ItemComponent, frame: &AARectangle, resolution: fsize) -> Vec<DiscreteLine> {
+    let above = (component.outer.bbox.y_max - frame.y_max).max(0.0);
+    let to_strip = Transformation::from_translation((-frame.x_min, -frame.y_max - above));
+    let mut lines = component.transform_clone(&to_strip).discretize(resolution);
+    for line in &mut lines {
+        line.occupied = line
+            .occupied
+            .iter()
+            .filter_map(|interval| {
+                let start = (interval.start - above).max(0.0);
+                let end = (interval.end - above).min(frame.height());
+                (start <= end).then_some(Interval { start, end, orientation: interval.orientation, total_space: end - start })
+            })
+            .collect();
+    }
+    lines
 }
 
 impl Interval{
@@ -63,7 +111,6 @@ impl Interval{
 
     #[inline(always)]
     pub fn overlaps(&self, other: &Interval) -> fsize {
-        OVERLAPS_COUNTER.fetch_add(1, Ordering::Relaxed);
         // Optimize spatial overlap check
         let spatial_overlap = if self.end == self.start || other.start == other.end {
             (self.end.min(other.end) - self.start.max(other.start)) > -EPSILON
@@ -76,13 +123,10 @@ impl Interval{
         }
         
         // If there is spatial overlap, check orientations
-        match (self.orientation, other.orientation) {
-            (Some(self_orient), Some(other_orient)) => {
-                if self_orient != other_orient {
-                    return 0.0;
-                }
+        if let (Some(self_orient), Some(other_orient)) = (self.orientation, other.orientation) {
+            if self_orient != other_orient {
+                return 0.0;
             }
-            _ => {}
         }
         self.end - other.start 
     }
@@ -98,11 +142,18 @@ impl Interval{
     }
 }
 
+impl Default for DiscreteLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DiscreteLine {
     
     pub fn new() -> Self {
         DiscreteLine{
-        occupied: Vec::new()
+        occupied: Vec::new(),
+        quality_zones: Vec::new(),
         }
     }
     
@@ -142,8 +193,28 @@ impl DiscreteLine {
         height - self.space()
     }
 
+    /// Shift needed to move the incoming line off the quality zones below `quality`.
+    /// Unlike between items, the orientations do not matter, an edge along the boundary of a zone lies in it.
+    #[inline(always)]
+    pub fn check_quality(&self, incoming_line: &DiscreteLine, offshoot: &fsize, quality: usize, overlap_checks: &Cell<usize>) -> fsize {
+        let mut max_shift_needed = 0.0;
+        for (zone_quality, zone) in &self.quality_zones {
+            if *zone_quality >= quality {
+                continue;
+            }
+            for incoming_interval in &incoming_line.occupied {
+                overlap_checks.set(overlap_checks.get() + 1);
+                let incoming_interval = incoming_interval.shifted(offshoot);
+                if incoming_interval.start < zone.end && zone.start < incoming_interval.end {
+                    max_shift_needed = max_shift_needed.max(zone.end - incoming_interval.start);
+                }
+            }
+        }
+        max_shift_needed
+    }
+
     #[inline(always)]
-    pub fn check_at(&self, height: &fsize, incoming_line: &DiscreteLine, offshoot: &fsize) -> Option<fsize>{
+    pub fn check_at(&self, height: &fsize, incoming_line: &DiscreteLine, offshoot: &fsize, overlap_checks: &Cell<usize>) -> Option<fsize>{
 
         let mut max_shift_needed = 0.0;
 
@@ -171,6 +242,7 @@ impl DiscreteLine {
                     break;
                 }
                 
+                overlap_checks.set(overlap_checks.get() + 1);
                 let shift = current.overlaps(&shifted_interval);
                 if shift > EPSILON {
                     max_shift_needed = max_shift_needed.max(shift);
@@ -193,99 +265,121 @@ impl DiscreteLine {
 }
 
 impl DiscreteStrip {
+    /// Strip of a fixed number of lines
+    pub fn new(lines: Vec<DiscreteLine>) -> Self {
+        let max_lines = lines.len();
+        DiscreteStrip { lines, max_lines, item_lines: 0, counters: StripCounters::default() }
+    }
+
+    /// Strip which starts with `lines` and grows up to `max_lines`
+    pub fn growing(lines: Vec<DiscreteLine>, max_lines: usize) -> Self {
+        DiscreteStrip { lines, max_lines, item_lines: 0, counters: StripCounters::default() }
+    }
+
+    /// The line at `index`, the lines the strip can still grow are empty
+    #[inline(always)]
+    fn line(&self, index: usize) -> &DiscreteLine {
+        self.lines.get(index).unwrap_or(&EMPTY_LINE)
+    }
 
     pub fn get_next_id(&self) -> usize {
         self.lines.len() + 1
     }
 
 
-    pub fn try_fit_segments(&mut self, polygon_sets: &Vec<&Vec<DiscreteLine>>, height: &fsize, resolution: &fsize, amount: &isize, item_id: &usize) -> Vec<(Transformation, usize)> {
+    /// Places `amount` copies of an item, an item with a `quality` can not be placed over zones of lower quality
+    pub fn try_fit_segments(&mut self, polygon_sets: &[&Vec<DiscreteLine>], height: &fsize, resolution: &fsize, amount: &isize, item_id: &usize, quality: Option<usize>) -> Vec<(Transformation, usize)> {
         if polygon_sets.is_empty() {
             return Vec::new();
         }
         
-        self.check_polygon(polygon_sets, height, resolution, amount, item_id)
+        self.check_polygon(polygon_sets, height, resolution, amount, item_id, quality)
     }
 
-    pub fn check_polygon(&mut self, polygon_sets: &Vec<&Vec<DiscreteLine>>, height: &fsize, resolution: &fsize, amount: &isize, item_id: &usize) -> Vec<(Transformation, usize)>{
+    pub fn check_polygon(&mut self, polygon_sets: &[&Vec<DiscreteLine>], height: &fsize, resolution: &fsize, amount: &isize, item_id: &usize, quality: Option<usize>) -> Vec<(Transformation, usize)>{
         if !is_x86_feature_detected!("avx2"){       
-            unsafe {return self.check_polygon_avx2(polygon_sets, height, resolution, amount, item_id);}
+            unsafe { self.check_polygon_avx2(polygon_sets, height, resolution, amount, item_id, quality) }
         } else{   
-            return self.check_polygon_scalar(polygon_sets, height, resolution, amount, item_id);
+            self.check_polygon_scalar(polygon_sets, height, resolution, amount, item_id, quality)
         }
     }
 
     #[target_feature(enable = "avx2")]
-    unsafe fn check_polygon_avx2(&mut self, polygon_sets: &Vec<&Vec<DiscreteLine>>, height: &fsize, resolution: &fsize, amount: &isize,  item_id: &usize) -> Vec<(Transformation, usize)>{
-        return self.check_polygon_scalar(polygon_sets, height, resolution, amount, item_id);
+    unsafe fn check_polygon_avx2(&mut self, polygon_sets: &[&Vec<DiscreteLine>], height: &fsize, resolution: &fsize, amount: &isize,  item_id: &usize, quality: Option<usize>) -> Vec<(Transformation, usize)>{
+        self.check_polygon_scalar(polygon_sets, height, resolution, amount, item_id, quality)
     }
 
     #[inline(always)]
-    fn check_polygon_scalar(&mut self, polygon_sets: &Vec<&Vec<DiscreteLine>>, height: &fsize, resolution: &fsize, amount: &isize, item_id: &usize) -> Vec<(Transformation, usize)>{
+    fn check_polygon_scalar(&mut self, polygon_sets: &[&Vec<DiscreteLine>], height: &fsize, resolution: &fsize, amount: &isize, _item_id: &usize, quality: Option<usize>) -> Vec<(Transformation, usize)>{
         let mut results: Vec<(Transformation, usize)> = Vec::with_capacity(*amount as usize);
-        let start_time = Instant::now();
     
         let mut placed = *amount;
         let mut start_x = 0;
         let mut start_offshoot = 0.0;
 
         while placed > 0 {
-            let placing_time = Instant::now();
-            let mut best_placement = None;
-            // Process each set of segments
-            for (i,polygon) in polygon_sets.iter().enumerate(){
-                let placement = self.find_best_position(polygon, height, start_x, start_offshoot, &i);
-                match best_placement {
-                    None => {
-                        best_placement = placement;
-                    },
-                    Some((best_i0, best_offshoot, best_set_index)) => {
-                        let tentative = placement.unwrap();
-                        
-                        // Compare based on line index, then offshoot, then empty space
-                        if tentative.0 < best_i0 || 
-                        (tentative.0 == best_i0 && tentative.1 < best_offshoot) ||
-                        (tentative.0 == best_i0 && tentative.1 == best_offshoot && polygon_sets[tentative.2].first().unwrap().space() < polygon_sets[best_set_index].first().unwrap().space()) {
-                            best_placement = placement;
-                        }
-                    }
-                }
-            }
-            if let Some((best_i0, best_offshoot, best_set_index)) = best_placement {
+            if let Some((best_i0, best_offshoot, best_set_index)) = self.find_placement(polygon_sets, height, start_x, start_offshoot, quality) {
                 // Apply the selected placement
                 let selected_segments = polygon_sets[best_set_index];
                 self.add_segments(selected_segments, best_offshoot, best_i0);
                 start_x = best_i0;
                 start_offshoot = best_offshoot;
-                placed = placed - 1;
-                //println!("placing item {} {} took {:?} ",item_id ,placed , placing_time.elapsed());
+                placed -= 1;
                 results.push((Transformation::from_translation((best_i0 as fsize * resolution, height - best_offshoot)), best_set_index));
             } else{
-                println!("ERROR");
+                log::debug!("[SDR] no place left for item {}, {} copies not placed", _item_id, placed);
                 break;
             }
         }
-        //println!("checking took {:?} ", start_time.elapsed());
         results
     }
 
+    /// Finds the left-most, then top-most position over all sets of segments without occupying it.
+    /// Returns the first line, the offshoot and the index of the selected set.
+    pub fn find_placement(&self, polygon_sets: &[&Vec<DiscreteLine>], height: &fsize, start_x: usize, start_offshoot: fsize, quality: Option<usize>) -> Option<(usize, fsize, usize)> {
+        let mut best_placement: Option<(usize, fsize, usize)> = None;
+        // Process each set of segments
+        for (i, polygon) in polygon_sets.iter().enumerate() {
+            let Some(tentative) = self.find_best_position(polygon, height, start_x, start_offshoot, &i, quality) else {
+                continue;
+            };
+            match best_placement {
+                None => {
+                    best_placement = Some(tentative);
+                },
+                Some((best_i0, best_offshoot, best_set_index)) => {
+                    // Compare based on line index, then offshoot, then empty space
+                    if tentative.0 < best_i0 || 
+                    (tentative.0 == best_i0 && tentative.1 < best_offshoot) ||
+                    (tentative.0 == best_i0 && tentative.1 == best_offshoot && polygon_sets[tentative.2].first().unwrap().space() < polygon_sets[best_set_index].first().unwrap().space()) {
+                        best_placement = Some(tentative);
+                    }
+                }
+            }
+        }
+        best_placement
+    }
+
     #[inline(always)]
-    fn find_best_position(&self, polygon: &Vec<DiscreteLine>, height: &fsize, start_x: usize, start_offshoot: fsize, set_index: &usize) ->  Option<(usize, fsize, usize)>{
+    fn find_best_position(&self, polygon: &[DiscreteLine], height: &fsize, start_x: usize, start_offshoot: fsize, set_index: &usize, quality: Option<usize>) ->  Option<(usize, fsize, usize)>{
         // Skip empty segment sets
         let mut initial_offshoot = start_offshoot;
     
         let first: &DiscreteLine = &polygon[0];
+        // The polygon has to fit on the strip entirely, once it has grown as far as it can
+        let last_start = (self.lines.len().max(self.max_lines) + 1).checked_sub(polygon.len())?;
         
         // Try each available line starting from start_x
-        for line_idx in start_x..self.lines.len() {
-            let line = &self.lines[line_idx];
+        for line_idx in start_x..last_start {
+            let line = self.line(line_idx);
+            self.counters.lines_scanned.set(self.counters.lines_scanned.get() + 1);
             
             // Check if we can fit the first segment at this line
-            if let Some(offshoot_shift) = line.check_at(height, first, &initial_offshoot) {
+            if let Some(offshoot_shift) = line.check_at(height, first, &initial_offshoot, &self.counters.overlap_checks) {
                 let offshoot = initial_offshoot + offshoot_shift;
                 
                 // Now verify if all segments fit
-                if let Some(final_offshoot) = self.verify_all_segments(polygon, height, line_idx, offshoot) {
+                if let Some(final_offshoot) = self.verify_all_segments(polygon, height, line_idx, offshoot, quality) {
                     return Some((line_idx, final_offshoot, *set_index));
                 }
             }
@@ -297,16 +391,20 @@ impl DiscreteStrip {
     }
     
     #[inline(always)]
-    fn verify_all_segments(&self, polygon: &Vec<DiscreteLine>, height: &fsize, i0: usize, offshoot: fsize) -> Option<f32> {
+    fn verify_all_segments(&self, polygon: &[DiscreteLine], height: &fsize, i0: usize, offshoot: fsize, quality: Option<usize>) -> Option<f32> {
         let mut current_offshoot = offshoot;
         
-        'outer: loop {    
+        loop {    
             let mut all_fit = true;
             for (i, segment) in polygon.iter().enumerate() {                
                 // Check if segment fits at current line and offshoot
-                match self.lines[i0 + i].check_at(height, segment, &current_offshoot) {
+                match self.line(i0 + i).check_at(height, segment, &current_offshoot, &self.counters.overlap_checks) {
                     None => return None, // Segment doesn't fit
                     Some(shift) => {
+                        let shift = match quality {
+                            Some(quality) => shift.max(self.line(i0 + i).check_quality(segment, &current_offshoot, quality, &self.counters.overlap_checks)),
+                            None => shift,
+                        };
                         if shift > EPSILON {
                             // Need to shift and retry all segments
                             current_offshoot += shift;
@@ -326,8 +424,21 @@ impl DiscreteStrip {
         }
     }
 
+    /// Occupies the space of the segments of an item, the strip grows to hold them up to `max_lines`
     #[inline(always)]
-    pub fn add_segments(&mut self, segments: &Vec<DiscreteLine>, offshoot: fsize, firstline: usize) {
+    pub fn add_segments(&mut self, segments: &[DiscreteLine], offshoot: fsize, firstline: usize) {
+        self.occupy(segments, offshoot, firstline);
+        if let Some(last) = segments.iter().rposition(|segment| !segment.occupied.is_empty()) {
+            self.item_lines = self.item_lines.max((firstline + last + 1).min(self.lines.len()));
+        }
+    }
+
+    #[inline(always)]
+    fn occupy(&mut self, segments: &[DiscreteLine], offshoot: fsize, firstline: usize) {
+        let needed_lines = (firstline + segments.len()).min(self.max_lines);
+        if needed_lines > self.lines.len() {
+            self.lines.resize(needed_lines, DiscreteLine::new());
+        }
         for (j, segment) in segments.iter().enumerate() {
             if let Some(line) = self.lines.get_mut(firstline + j) {
                 // Process each interval from the segment
@@ -347,18 +458,40 @@ impl DiscreteStrip {
         }
     }  
 
+    /// Occupies the space covered by obstacles, e.g. defects in the material or the exterior of a bin.
+    /// `frame` is the rectangle spanned by the lines, in the coordinates of the obstacles: the first line lies on
+    /// its left side and offshoot 0 on its top.
+    pub fn add_obstacles(&mut self, obstacles: &[ItemComponent], frame: &AARectangle, resolution: fsize) {
+        for obstacle in obstacles {
+            let lines = discretize_in_frame(obstacle, frame, resolution);
+            self.occupy(&lines, 0.0, 0);
+        }
+    }
+
+    /// Marks the spans of the lines lying in inferior quality zones, `frame` as in `add_obstacles`
+    pub fn add_quality_zones(&mut self, quality_zones: &[InferiorQualityZone], frame: &AARectangle, resolution: fsize) {
+        for quality_zone in quality_zones {
+            for zone in &quality_zone.zones {
+                let component = ItemComponent { outer: zone.as_ref().clone(), holes: vec![] };
+                for (line, zone_line) in self.lines.iter_mut().zip(discretize_in_frame(&component, frame, resolution)) {
+                    line.quality_zones.extend(zone_line.occupied.into_iter().map(|interval| (quality_zone.quality, interval)));
+                }
+            }
+        }
+    }
+
+    /// Total occupied space over all lines
+    pub fn occupied_space(&self) -> fsize {
+        self.lines.iter().map(|line| line.space()).sum()
+    }
+
+    /// Drops the lines after the last one holding an item, obstacles beyond the items do not widen the strip
     pub fn trim_after_last_occupied(&mut self) {
-        // Find the index of the last line with non-empty occupied list
-        let last_occupied_index = self.lines.iter()
-            .enumerate()
-            .rfind(|(_, line)| !line.occupied.is_empty())
-            .map(|(index, _)| index);
-        
-        if let Some(index) = last_occupied_index {
-            // Keep only up to and including the last occupied line
-            self.lines.truncate(index + 1);
+        if self.item_lines > 0 {
+            // Keep only up to and including the last line holding an item
+            self.lines.truncate(self.item_lines);
         }
-        // If no occupied lines are found, keep all lines as they are
+        // If no item is placed, keep all lines as they are
     }
 
 
@@ -0,0 +1,128 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use jagua_rs::fsize;
+use jagua_rs::geometry::primitives::simple_polygon::SimplePolygon;
+
+use crate::discrete_item::ItemGeometry;
+use crate::discrete_line::DiscreteLine;
+
+/// Changes whenever the discretization changes, so the lines cached by an older version are not loaded anymore
+const CACHE_VERSION: u64 = 1;
+
+/// On-disk cache of the discretized orientations of items, one bincode file per orientation.
+/// The files are named after a hash of everything the lines depend on: the points of the shape and of its holes
+/// and components, the rotation, the resolution, the spacing and the tolerance of the polygon simplification.
+/// Files which cannot be read are discretized again, files which cannot be written are skipped.
+#[derive(Debug, Clone)]
+pub struct DiscretizationCache {
+    dir: PathBuf,
+    /// Tolerance of the simplification of the shapes, if any
+    simplification_tolerance: Option<fsize>,
+}
+
+impl DiscretizationCache {
+    pub fn new(dir: &Path, simplification_tolerance: Option<fsize>) -> Self {
+        Self { dir: dir.to_path_buf(), simplification_tolerance }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Key of an orientation of a shape, stable across runs and platforms
+    pub fn key(&self, shape: &SimplePolygon, geometry: &ItemGeometry, rotation: fsize, half_spacing: fsize, resolution: fsize) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write_u64(CACHE_VERSION);
+        hasher.write_polygon(shape);
+        hasher.write_u64(geometry.holes.len() as u64);
+        for hole in &geometry.holes {
+            hasher.write_polygon(hole);
+        }
+        hasher.write_u64(geometry.components.len() as u64);
+        for component in &geometry.components {
+            hasher.write_polygon(&component.outer);
+            hasher.write_u64(component.holes.len() as u64);
+            for hole in &component.holes {
+                hasher.write_polygon(hole);
+            }
+        }
+        for value in [rotation, half_spacing, resolution, self.simplification_tolerance.unwrap_or(-1.0)] {
+            hasher.write_fsize(value);
+        }
+        hasher.0
+    }
+
+    /// Lines cached under `key`, if there are any and they can be read
+    pub fn load(&self, key: u64) -> Option<Vec<DiscreteLine>> {
+        let path = self.path(key);
+        let file = File::open(&path).ok()?;
+        match bincode::deserialize_from(BufReader::new(file)) {
+            Ok(lines) => Some(lines),
+            Err(err) => {
+                log::warn!("[CACHE] ignoring unreadable {}: {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Caches the lines under `key`. They are written to a temporary file first, so concurrent runs sharing
+    /// the cache never read a partial file.
+    pub fn store(&self, key: u64, lines: &[DiscreteLine]) {
+        let path = self.path(key);
+        let tmp_path = self.dir.join(format!("{:016x}.{}.tmp", key, std::process::id()));
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|_| File::create(&tmp_path))
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                bincode::serialize_into(&mut writer, lines).map_err(io::Error::other)?;
+                writer.flush()
+            })
+            .and_then(|_| fs::rename(&tmp_path, &path));
+        if let Err(err) = written {
+            log::warn!("[CACHE] could not write {}: {}", path.display(), err);
+            let _ = fs::remove_file(&tmp_path);
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+/// 64-bit FNV-1a, unlike `DefaultHasher` it is guaranteed not to change between releases of Rust
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_fsize(&mut self, value: fsize) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_polygon(&mut self, polygon: &SimplePolygon) {
+        self.write_u64(polygon.points.len() as u64);
+        for point in &polygon.points {
+            self.write_fsize(point.0);
+            self.write_fsize(point.1);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::Parser;
+use jagua_rs::fsize;
 use log::LevelFilter;
 
 #[derive(Parser, Debug)]
@@ -12,6 +14,18 @@ pub struct Cli {
     pub solution_folder: PathBuf,
     #[arg(short, long, value_name = "FILE")]
     pub config_file: Option<PathBuf>,
+    /// Distance between the discrete lines. If undefined, it is advised from the geometry of the instance
+    #[arg(short, long)]
+    pub resolution: Option<fsize>,
+    /// Lines over the estimated width of the strip which the advised resolution aims for
+    #[arg(long, default_value_t = 256)]
+    pub target_lines: usize,
+    /// Time in seconds which a solve at the advised resolution should take, instead of `target_lines`
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub time_budget: Option<Duration>,
+    /// Folder in which the discretized items are cached between runs
+    #[arg(long, value_name = "FOLDER")]
+    pub cache_dir: Option<PathBuf>,
     #[arg(
         short,
         long,
@@ -20,3 +34,9 @@ pub struct Cli {
     )]
     pub log_level: LevelFilter,
 }
+
+/// Duration of a number of seconds which is neither negative nor too large
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs = arg.parse::<f64>().map_err(|err| err.to_string())?;
+    Duration::try_from_secs_f64(secs).map_err(|err| err.to_string())
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use jagua_rs::io::json_instance::{JsonQualityZone, JsonShape};
+
+/// Fields of a JSON instance which are specific to SDR, jagua-rs ignores them
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonInstanceExt {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub strip: Option<JsonStripExt>,
+    /// Fields of the items, in the same order as the items of the instance
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub items: Vec<JsonItemExt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonStripExt {
+    /// Defects in the material of the strip, no item can be placed over them
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub defects: Vec<JsonShape>,
+    /// Zones of inferior quality in the strip, as for bins
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub zones: Vec<JsonQualityZone>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonItemExt {
+    /// The item may be mirrored, e.g. when both sides of the material can be used
+    #[serde(default)]
+    pub allow_mirroring: bool,
+}
@@ -1,15 +1,95 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
 use serde::{Deserialize, Serialize};
 
+use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
+use jagua_rs::entities::placed_item::PItemKey;
+use jagua_rs::entities::solution::Solution;
+use jagua_rs::fsize;
+use jagua_rs::geometry::transformation::Transformation;
 use jagua_rs::io::json_instance::JsonInstance;
-use jagua_rs::io::json_solution::JsonSolution;
+use jagua_rs::io::json_solution::{JsonContainer, JsonLayoutStats, JsonPlacedItem, JsonTransformation};
+use jagua_rs::io::parser;
 
 use crate::sdr_config::SDRConfig;
+use crate::sdr_parse::SdrInstance;
+use crate::solve_report::SolveReport;
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct JsonOutput {
     #[serde(flatten)]
     pub instance: JsonInstance,
-    pub solution: JsonSolution,
+    pub solution: JsonSdrSolution,
     pub config: SDRConfig,
+    #[serde(default)]
+    pub report: SolveReport,
+}
+
+/// Solution in the format of jagua-rs, whose placed items can be mirrored
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonSdrSolution {
+    pub usage: fsize,
+    pub run_time_sec: u64,
+    pub layouts: Vec<JsonSdrLayout>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonSdrLayout {
+    pub container: JsonContainer,
+    pub placed_items: Vec<JsonSdrPlacedItem>,
+    pub statistics: JsonLayoutStats,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct JsonSdrPlacedItem {
+    #[serde(flatten)]
+    pub placed_item: JsonPlacedItem,
+    /// The shape of the item is mirrored along the y-axis (x -> -x) before the transformation is applied
+    #[serde(default)]
+    pub mirrored: bool,
+}
+
+impl JsonSdrSolution {
+    /// Composes the solution like `parser::compose_json_solution`, `mirrored_items` holds the layout id and key of
+    /// the placed items which are mirrored
+    pub fn compose(solution: &Solution, instance: &SdrInstance, mirrored_items: &HashSet<(usize, PItemKey)>, epoch: Instant) -> Self {
+        let json_solution = parser::compose_json_solution(solution, &instance.instance, epoch);
+        let layouts = json_solution
+            .layouts
+            .into_iter()
+            .zip(&solution.layout_snapshots)
+            .map(|(json_layout, s_layout)| {
+                // jagua-rs lists the placed items in the order of the snapshot
+                let placed_items = json_layout
+                    .placed_items
+                    .into_iter()
+                    .zip(s_layout.placed_items.iter())
+                    .map(|(placed_item, (pik, pi))| match mirrored_items.contains(&(s_layout.id, pik)) {
+                        false => JsonSdrPlacedItem { placed_item, mirrored: false },
+                        true => {
+                            // the item is mirrored in its pretransformed frame, which is the same as mirroring the
+                            // original shape and then applying the pretransform mirrored as well
+                            let pretransf = instance.instance.item(pi.item_id).pretransform.decompose();
+                            let (tx, ty) = pretransf.translation();
+                            let abs_transf = Transformation::empty()
+                                .rotate(-pretransf.rotation())
+                                .translate((-tx, ty))
+                                .transform_from_decomposed(&pi.d_transf)
+                                .transform(&s_layout.bin.pretransform.clone().inverse())
+                                .decompose();
+                            let transformation = JsonTransformation { rotation: abs_transf.rotation(), translation: abs_transf.translation() };
+                            JsonSdrPlacedItem { placed_item: JsonPlacedItem { transformation, ..placed_item }, mirrored: true }
+                        }
+                    })
+                    .collect();
+                JsonSdrLayout { container: json_layout.container, placed_items, statistics: json_layout.statistics }
+            })
+            .collect();
+        Self { usage: json_solution.usage, run_time_sec: json_solution.run_time_sec, layouts }
+    }
 }
@@ -1,16 +1,35 @@
+use crate::discrete_item::ItemGeometry;
+use crate::discrete_line::DiscreteStrip;
 use crate::io::svg_util::SvgDrawOptions;
 use crate::io::{svg_export, svg_util};
+use crate::sdr_parse::SdrInstance;
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
 use jagua_rs::entities::instances::instance::Instance;
 use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
 use jagua_rs::entities::layout::Layout;
 use jagua_rs::entities::layout::LayoutSnapshot;
+use jagua_rs::entities::placed_item::PItemKey;
 use jagua_rs::fsize;
+use jagua_rs::geometry::primitives::aa_rectangle::AARectangle;
 use jagua_rs::geometry::primitives::circle::Circle;
 use jagua_rs::geometry::transformation::Transformation;
 use jagua_rs::io::parser;
 use svg::Document;
+use svg::node::element::path::Data;
 use svg::node::element::{Definitions, Group, Title, Use};
 
+/// Discretized strip on which the items of a layout were placed, see `SDROptimizer::strip_overlay`
+#[derive(Debug, Clone)]
+pub struct StripOverlay<'a> {
+    pub discrete_strip: &'a DiscreteStrip,
+    /// Rectangle spanned by the strip in the internal coordinates of the layout: line `i` lies at
+    /// x = `frame.x_min + i * resolution` and its intervals hold the distance below `frame.y_max`
+    pub frame: AARectangle,
+    pub resolution: fsize,
+}
+
 pub fn s_layout_to_svg(
     s_layout: &LayoutSnapshot,
     instance: &Instance,
@@ -20,7 +39,63 @@ pub fn s_layout_to_svg(
     layout_to_svg(&layout, instance, options)
 }
 
+/// Draws a layout with the shapes of the items known to jagua-rs, items made of several polygons are drawn as their
+/// convex hull, without holes, and no item is mirrored. See `sdr_layout_to_svg` for the exact geometry.
 pub fn layout_to_svg(layout: &Layout, instance: &Instance, options: SvgDrawOptions) -> Document {
+    draw_layout(layout, instance, &HashMap::new(), &HashSet::new(), None, options)
+}
+
+pub fn s_sdr_layout_to_svg(
+    s_layout: &LayoutSnapshot,
+    instance: &SdrInstance,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    options: SvgDrawOptions,
+) -> Document {
+    s_sdr_layout_to_svg_with_strip(s_layout, instance, mirrored_items, None, options)
+}
+
+/// Same as `s_sdr_layout_to_svg`, with the discretized strip of the layout drawn on top if `options.discrete_strip` is set
+pub fn s_sdr_layout_to_svg_with_strip(
+    s_layout: &LayoutSnapshot,
+    instance: &SdrInstance,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    strip: Option<StripOverlay>,
+    options: SvgDrawOptions,
+) -> Document {
+    let layout = Layout::from_snapshot(s_layout);
+    sdr_layout_to_svg_with_strip(&layout, instance, mirrored_items, strip, options)
+}
+
+/// Draws a layout with the holes and components of the items, `mirrored_items` holds the layout id and key of the
+/// placed items which are mirrored
+pub fn sdr_layout_to_svg(
+    layout: &Layout,
+    sdr_instance: &SdrInstance,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    options: SvgDrawOptions,
+) -> Document {
+    sdr_layout_to_svg_with_strip(layout, sdr_instance, mirrored_items, None, options)
+}
+
+/// Same as `sdr_layout_to_svg`, with the discretized strip of the layout drawn on top if `options.discrete_strip` is set
+pub fn sdr_layout_to_svg_with_strip(
+    layout: &Layout,
+    sdr_instance: &SdrInstance,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    strip: Option<StripOverlay>,
+    options: SvgDrawOptions,
+) -> Document {
+    draw_layout(layout, &sdr_instance.instance, &sdr_instance.geometry, mirrored_items, strip, options)
+}
+
+fn draw_layout(
+    layout: &Layout,
+    instance: &Instance,
+    geometry: &HashMap<usize, ItemGeometry>,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    strip: Option<StripOverlay>,
+    options: SvgDrawOptions,
+) -> Document {
     let internal_bin = &layout.bin;
     let inv_bin_transf = internal_bin.pretransform.clone().inverse();
     let bin = parser::pretransform_bin(internal_bin, &inv_bin_transf);
@@ -110,17 +185,32 @@ pub fn layout_to_svg(layout: &Layout, instance: &Instance, options: SvgDrawOptio
                 &internal_item.pretransform.clone().inverse(),
             );
             let shape = item.shape.as_ref();
+            // the shape of items with several components is only their convex hull, draw the components instead
+            let outlines = match geometry.get(&item.id) {
+                Some(geometry) => {
+                    let geometry = geometry.transform_clone(&internal_item.pretransform.clone().inverse());
+                    match geometry.components.is_empty() {
+                        true => [shape.clone()].into_iter().chain(geometry.holes).collect_vec(),
+                        false => geometry
+                            .components
+                            .into_iter()
+                            .flat_map(|component| [component.outer].into_iter().chain(component.holes))
+                            .collect_vec(),
+                    }
+                }
+                None => vec![shape.clone()],
+            };
             let color = match item.base_quality {
                 None => theme.item_fill.to_owned(),
                 Some(q) => svg_util::blend_colors(theme.item_fill, theme.qz_fill[q]),
             };
             item_defs = item_defs.add(Group::new().set("id", format!("item_{}", item.id)).add(
                 svg_export::data_to_path(
-                    svg_export::simple_polygon_data(shape),
+                    svg_export::polygons_data(&outlines),
                     &[
                         ("fill", &*format!("{}", color)),
                         ("stroke-width", &*format!("{}", stroke_width)),
-                        ("fill-rule", "nonzero"),
+                        ("fill-rule", "evenodd"),
                         ("stroke", "black"),
                         ("opacity", "0.9"),
                     ],
@@ -179,19 +269,42 @@ pub fn layout_to_svg(layout: &Layout, instance: &Instance, options: SvgDrawOptio
         let mut items_group = Group::new().set("id", "items").add(item_defs);
         let mut surrogate_group = Group::new().set("id", "surrogates").add(surrogate_defs);
 
-        for pi in layout.placed_items().values() {
-            let abs_transf = parser::internal_to_absolute_transform(
-                &pi.d_transf,
-                &instance.item(pi.item_id).pretransform,
-                &internal_bin.pretransform,
-            );
+        for (pik, pi) in layout.placed_items().iter() {
+            let item_pretransf = &instance.item(pi.item_id).pretransform;
+            let mirrored = mirrored_items.contains(&(layout.id(), pik));
+            let (abs_transf, svg_transf) = match mirrored {
+                false => {
+                    let abs_transf = parser::internal_to_absolute_transform(
+                        &pi.d_transf,
+                        item_pretransf,
+                        &internal_bin.pretransform,
+                    );
+                    let svg_transf = transform_to_svg(&abs_transf);
+                    (abs_transf, svg_transf)
+                }
+                true => {
+                    //the item is mirrored in its pretransformed frame, between the pretransform and the placement
+                    let abs_transf = parser::internal_to_absolute_transform(
+                        &pi.d_transf,
+                        &Transformation::empty(),
+                        &internal_bin.pretransform,
+                    );
+                    let svg_transf = format!(
+                        "{}, scale(-1 1), {}",
+                        transform_to_svg(&abs_transf),
+                        transform_to_svg(item_pretransf)
+                    );
+                    (abs_transf, svg_transf)
+                }
+            };
             let title = Title::new(format!(
-                "item, id: {}, transf: [{}]",
+                "item, id: {}, transf: [{}]{}",
                 pi.item_id,
-                abs_transf.decompose()
+                abs_transf.decompose(),
+                if mirrored { ", mirrored" } else { "" }
             ));
             let pi_ref = Use::new()
-                .set("transform", transform_to_svg(&abs_transf))
+                .set("transform", svg_transf.clone())
                 .set("xlink:href", format!("#item_{}", pi.item_id))
                 .add(title);
 
@@ -199,7 +312,7 @@ pub fn layout_to_svg(layout: &Layout, instance: &Instance, options: SvgDrawOptio
 
             if options.surrogate {
                 let pi_surr_ref = Use::new()
-                    .set("transform", transform_to_svg(&abs_transf))
+                    .set("transform", svg_transf)
                     .set("xlink:href", format!("#surrogate_{}", pi.item_id));
 
                 surrogate_group = surrogate_group.add(pi_surr_ref);
@@ -279,9 +392,59 @@ pub fn layout_to_svg(layout: &Layout, instance: &Instance, options: SvgDrawOptio
         }
     };
 
+    let strip_group = match (options.discrete_strip, strip) {
+        (true, Some(strip)) => {
+            let StripOverlay { discrete_strip, frame, resolution } = strip;
+            // the lines, and their intervals without orientation, on the left and on the right of a vertex
+            let mut lines_data = Data::new();
+            let mut intervals_data = [Data::new(), Data::new(), Data::new()];
+            for (i, line) in discrete_strip.lines.iter().enumerate() {
+                let x = frame.x_min + i as fsize * resolution;
+                lines_data = lines_data.move_to((x, frame.y_min)).line_to((x, frame.y_max));
+                for interval in &line.occupied {
+                    let index = match interval.orientation {
+                        None => 0,
+                        Some(false) => 1,
+                        Some(true) => 2,
+                    };
+                    intervals_data[index] = std::mem::take(&mut intervals_data[index])
+                        .move_to((x, frame.y_max - interval.start))
+                        .line_to((x, frame.y_max - interval.end));
+                }
+            }
+            let interval_width = fsize::min(stroke_width, resolution / 2.0);
+            let mut strip_group = Group::new()
+                .set("id", "discrete_strip")
+                .set("transform", transform_to_svg(&inv_bin_transf))
+                .add(svg_export::data_to_path(
+                    lines_data,
+                    &[
+                        ("fill", "none"),
+                        ("stroke", "gray"),
+                        ("stroke-width", &*format!("{}", interval_width * 0.25)),
+                        ("stroke-opacity", "0.5"),
+                    ],
+                ));
+            for (data, color) in intervals_data.into_iter().zip(["black", "red", "blue"]).filter(|(data, _)| !data.is_empty()) {
+                strip_group = strip_group.add(svg_export::data_to_path(
+                    data,
+                    &[
+                        ("fill", "none"),
+                        ("stroke", color),
+                        ("stroke-width", &*format!("{}", interval_width)),
+                        ("stroke-opacity", "0.8"),
+                        ("stroke-linecap", "round"),
+                    ],
+                ));
+            }
+            Some(strip_group)
+        }
+        _ => None,
+    };
+
     let vbox_svg = (vbox.x_min, vbox.y_min, vbox.width(), vbox.height());
 
-    let optionals = [surrogate_group, qt_group, hpg_group]
+    let optionals = [surrogate_group, qt_group, hpg_group, strip_group]
         .into_iter()
         .flatten()
         .fold(Group::new().set("id", "optionals"), |g, opt| g.add(opt));
@@ -1,57 +1,85 @@
 use std::fs;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 
 use log::{log, Level, LevelFilter};
+use serde::de::DeserializeOwned;
 use svg::Document;
 
 use jagua_rs::io::json_instance::JsonInstance;
 
+use crate::io::json_instance_ext::JsonInstanceExt;
 use crate::io::json_output::JsonOutput;
+use crate::sdr_config::SDRConfig;
+use crate::sdr_error::SdrError;
 use crate::EPOCH;
 
 pub mod cli;
+pub mod json_instance_ext;
 pub mod json_output;
 pub mod layout_to_svg;
 pub mod svg_export;
 pub mod svg_util;
 
 pub fn read_json_instance(path: &Path) -> JsonInstance {
-    let file = File::open(path)
-        .unwrap_or_else(|err| panic!("could not open instance file: {}, {}", path.display(), err));
+    try_read_json_instance(path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_read_json_instance(path: &Path) -> Result<JsonInstance, SdrError> {
+    read_json(path)
+}
+
+/// Reads the fields of an instance file which are specific to SDR
+pub fn read_json_instance_ext(path: &Path) -> JsonInstanceExt {
+    try_read_json_instance_ext(path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_read_json_instance_ext(path: &Path) -> Result<JsonInstanceExt, SdrError> {
+    read_json(path)
+}
+
+pub fn try_read_config(path: &Path) -> Result<SDRConfig, SdrError> {
+    read_json(path)
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, SdrError> {
+    let file = File::open(path).map_err(|source| SdrError::Io { path: path.to_path_buf(), source })?;
     let reader = BufReader::new(file);
-    serde_json::from_reader(reader)
-        .unwrap_or_else(|err| panic!("could not parse instance file: {}, {}", path.display(), err))
+    serde_json::from_reader(reader).map_err(|source| SdrError::Json { path: path.to_path_buf(), source })
 }
 
 pub fn write_json_output(json_output: &JsonOutput, path: &Path) {
-    let file = File::create(path)
-        .unwrap_or_else(|_| panic!("could not open solution file: {}", path.display()));
+    try_write_json_output(json_output, path).unwrap_or_else(|err| panic!("{}", err))
+}
 
-    let writer = BufWriter::new(file);
+pub fn try_write_json_output(json_output: &JsonOutput, path: &Path) -> Result<(), SdrError> {
+    let file = File::create(path).map_err(|source| SdrError::Io { path: path.to_path_buf(), source })?;
 
-    serde_json::to_writer_pretty(writer, &json_output)
-        .unwrap_or_else(|_| panic!("could not write solution file: {}", path.display()));
+    let mut writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(&mut writer, &json_output)
+        .map_err(|source| SdrError::Json { path: path.to_path_buf(), source })?;
+    writer.flush().map_err(|source| SdrError::Io { path: path.to_path_buf(), source })?;
 
     log::info!(
         "Solution JSON written to file://{}",
-        fs::canonicalize(path)
-            .expect("could not canonicalize path")
-            .to_str()
-            .unwrap()
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).display()
     );
+    Ok(())
 }
 
 pub fn write_svg(document: &Document, path: &Path) {
-    svg::save(path, document).expect("failed to write svg file");
+    try_write_svg(document, path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_write_svg(document: &Document, path: &Path) -> Result<(), SdrError> {
+    svg::save(path, document).map_err(|source| SdrError::Io { path: path.to_path_buf(), source })?;
     log::info!(
         "Solution SVG written to file://{}",
-        fs::canonicalize(path)
-            .expect("could not canonicalize path")
-            .to_str()
-            .unwrap()
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).display()
     );
+    Ok(())
 }
 
 pub fn init_logger(level_filter: LevelFilter) {
@@ -17,6 +17,20 @@ pub fn simple_polygon_data(s_poly: &SimplePolygon) -> Data {
     data.close()
 }
 
+/// Path data of several polygons in a single path, e.g. outer boundaries and their holes.
+/// To be drawn with the `evenodd` fill rule
+pub fn polygons_data(polygons: &[SimplePolygon]) -> Data {
+    let mut data = Data::new();
+    for s_poly in polygons {
+        data = data.move_to::<(fsize, fsize)>(s_poly.get_point(0).into());
+        for i in 1..s_poly.number_of_points() {
+            data = data.line_to::<(fsize, fsize)>(s_poly.get_point(i).into());
+        }
+        data = data.close();
+    }
+    data
+}
+
 pub fn quad_tree_data(qt_root: &QTNode, irrelevant_hazards: &[HazardEntity]) -> (Data, Data, Data) {
     qt_node_data(
         qt_root,
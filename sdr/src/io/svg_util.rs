@@ -1,11 +1,13 @@
-use std::convert::Into;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use jagua_rs::entities::quality_zone::N_QUALITIES;
 use jagua_rs::fsize;
 
+use crate::sdr_error::SdrError;
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Copy, Default)]
 pub struct SvgDrawOptions {
     ///The theme to use for the svg
@@ -20,6 +22,11 @@ pub struct SvgDrawOptions {
     ///Draw the fail fast surrogate on top of each item
     #[serde(default)]
     pub surrogate: bool,
+    ///Draw the lines of the discretized strip on top, with their occupied intervals coloured by orientation:
+    ///red on the left of a vertex, blue on the right, black for the others.
+    ///Only drawn when the strip is passed along, see `sdr_layout_to_svg_with_strip`
+    #[serde(default)]
+    pub discrete_strip: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Copy)]
@@ -42,20 +49,20 @@ impl SvgLayoutTheme {
     pub fn earth_tones() -> Self {
         SvgLayoutTheme {
             stroke_width_multiplier: 2.0,
-            bin_fill: "#CC824A".into(),
-            item_fill: "#FFC879".into(),
-            hole_fill: "#2D2D2D".into(),
+            bin_fill: Color::from_hex(0xCC824A),
+            item_fill: Color::from_hex(0xFFC879),
+            hole_fill: Color::from_hex(0x2D2D2D),
             qz_fill: [
-                "#000000".into(), //BLACK
-                "#FF0000".into(), //RED
-                "#FF5E00".into(), //ORANGE
-                "#FFA500".into(), //LIGHT ORANGE
-                "#C7A900".into(), //DARK YELLOW
-                "#FFFF00".into(), //YELLOW
-                "#CBFF00".into(), //GREEN
-                "#CBFF00".into(), //GREEN
-                "#CBFF00".into(), //GREEN
-                "#CBFF00".into(), //GREEN
+                Color::from_hex(0x000000), //BLACK
+                Color::from_hex(0xFF0000), //RED
+                Color::from_hex(0xFF5E00), //ORANGE
+                Color::from_hex(0xFFA500), //LIGHT ORANGE
+                Color::from_hex(0xC7A900), //DARK YELLOW
+                Color::from_hex(0xFFFF00), //YELLOW
+                Color::from_hex(0xCBFF00), //GREEN
+                Color::from_hex(0xCBFF00), //GREEN
+                Color::from_hex(0xCBFF00), //GREEN
+                Color::from_hex(0xCBFF00), //GREEN
             ],
             qz_stroke_opac: 0.5,
         }
@@ -64,20 +71,20 @@ impl SvgLayoutTheme {
     pub fn gray() -> Self {
         SvgLayoutTheme {
             stroke_width_multiplier: 2.5,
-            bin_fill: "#C3C3C3".into(),
-            item_fill: "#8F8F8F".into(),
-            hole_fill: "#FFFFFF".into(),
+            bin_fill: Color::from_hex(0xC3C3C3),
+            item_fill: Color::from_hex(0x8F8F8F),
+            hole_fill: Color::from_hex(0xFFFFFF),
             qz_fill: [
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
-                "#636363".into(), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
+                Color::from_hex(0x636363), //GRAY
             ],
             qz_stroke_opac: 0.9,
         }
@@ -108,27 +115,29 @@ pub fn blend_colors(color_1: Color, color_2: Color) -> Color {
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Color(u8, u8, u8);
 
+impl Color {
+    /// Color of the form `0xRRGGBB`, use `parse` for strings of the form `#RRGGBB`
+    pub const fn from_hex(rgb: u32) -> Self {
+        Color((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
 impl Display for Color {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
     }
 }
 
-impl From<String> for Color {
-    fn from(mut s: String) -> Self {
-        if s.starts_with('#') {
-            s.remove(0);
-        }
-        let r = u8::from_str_radix(&s[0..2], 16).unwrap();
-        let g = u8::from_str_radix(&s[2..4], 16).unwrap();
-        let b = u8::from_str_radix(&s[4..6], 16).unwrap();
-        Color(r, g, b)
-    }
-}
+impl FromStr for Color {
+    type Err = SdrError;
 
-impl From<&str> for Color {
-    fn from(s: &str) -> Self {
-        Color::from(s.to_owned())
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(SdrError::InvalidColor(s.to_owned()));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| SdrError::InvalidColor(s.to_owned()));
+        Ok(Color(channel(0)?, channel(2)?, channel(4)?))
     }
 }
 
@@ -147,6 +156,6 @@ impl<'de> Deserialize<'de> for Color {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(Color::from(s))
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
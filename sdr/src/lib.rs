@@ -4,10 +4,18 @@ use std::time::Instant;
 use once_cell::sync::Lazy;
 
 pub mod io;
+pub mod brkga;
+pub mod compaction;
 pub mod discrete_line;
 pub mod discrete_item;
+pub mod discretization_cache;
 pub mod sdr_config;
+pub mod sdr_error;
 pub mod sdr_optimizer;
 pub mod sdr_parse;
+pub mod resolution_advisor;
+pub mod solve_report;
+pub mod terminator;
+pub mod validation;
 
 pub static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
@@ -1,25 +1,89 @@
+use std::fs;
+use std::process;
 
-use sdr::sdr_parse::SdrParse;
+use clap::Parser as ClapParser;
+use log::{error, info, warn};
+
+use sdr::io::cli::Cli;
+use sdr::io::json_output::{JsonOutput, JsonSdrSolution};
+use sdr::io::layout_to_svg::s_sdr_layout_to_svg_with_strip;
+use sdr::io;
+use sdr::resolution_advisor::ResolutionTarget;
 use sdr::sdr_config::SDRConfig;
+use sdr::sdr_error::SdrError;
 use sdr::sdr_optimizer::SDROptimizer;
-use sdr::io::{self};
-use std::path::Path as OtherPath;
-use jagua_rs::util::polygon_simplification::PolySimplConfig;
-
+use sdr::sdr_parse::SdrParse;
+use sdr::EPOCH;
 
 fn main() {
-    let instance = OtherPath::new("../assets/shirts.json");
-    // parse the instance
-    let mut config = SDRConfig::default();
-    config.n_samples = 100;
-    let json_instance = io::read_json_instance(&instance);
-    let poly_simpl_config = match config.poly_simpl_tolerance {
-        Some(tolerance) => PolySimplConfig::Enabled { tolerance },
-        None => PolySimplConfig::Disabled,
+    let args = Cli::parse();
+    io::init_logger(args.log_level);
+
+    if let Err(err) = run(args) {
+        error!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: Cli) -> Result<(), SdrError> {
+    let config = match &args.config_file {
+        None => {
+            warn!("No config file provided, use --config-file to provide a custom config");
+            SDRConfig::default()
+        }
+        Some(config_file) => io::try_read_config(config_file)?,
     };
+    info!("Successfully parsed SDRConfig: {:?}", config);
+
+    let input_file_stem = args.input_file.file_stem().unwrap_or_default().to_string_lossy();
+
+    if !args.solution_folder.exists() {
+        fs::create_dir_all(&args.solution_folder)
+            .map_err(|source| SdrError::Io { path: args.solution_folder.clone(), source })?;
+    }
+
+    let json_instance = io::try_read_json_instance(&args.input_file)?;
+    let json_ext = io::try_read_json_instance_ext(&args.input_file)?;
+
+    let sdr_parser = match &args.cache_dir {
+        Some(cache_dir) => SdrParse::from_config(&config, true).with_discretization_cache(cache_dir),
+        None => SdrParse::from_config(&config, true),
+    };
+    let (instance, resolution) = match args.resolution {
+        Some(resolution) => (sdr_parser.try_parse_with_ext(&json_instance, &json_ext, resolution)?, resolution),
+        None => {
+            let target = match args.time_budget {
+                Some(budget) => ResolutionTarget::TimeBudget(budget),
+                None => ResolutionTarget::Lines(args.target_lines),
+            };
+            let (instance, advice) = sdr_parser.try_parse_advised(&json_instance, &json_ext, &config, target)?;
+            info!(
+                "Advised resolution {}, over approximating the items by {:.1}%",
+                advice.resolution,
+                advice.over_approximation_ratio * 100.0
+            );
+            (instance, advice.resolution)
+        }
+    };
+
+    let mut optimizer = SDROptimizer::try_new(instance, config, resolution)?;
+    let solution = optimizer.try_minimize_strip()?;
+
+    let json_output = JsonOutput {
+        instance: json_instance.clone(),
+        solution: JsonSdrSolution::compose(&solution, &optimizer.instance, &optimizer.mirrored_items, *EPOCH),
+        config,
+        report: optimizer.report.clone(),
+    };
+
+    let solution_path = args.solution_folder.join(format!("sol_{}.json", input_file_stem));
+    io::try_write_json_output(&json_output, &solution_path)?;
 
-    let sdr_parser = SdrParse::new(poly_simpl_config, config.cde_config, true);
-    let instance = sdr_parser.parse(&json_instance, 1.0);
-    let mut optimizer = SDROptimizer::new(instance, config, 1.0);
-    let _sol = optimizer.solve();
+    for (i, s_layout) in solution.layout_snapshots.iter().enumerate() {
+        let svg_path = args.solution_folder.join(format!("sol_{}_{}.svg", input_file_stem, i));
+        let strip = optimizer.strip_overlay(s_layout.id);
+        let svg = s_sdr_layout_to_svg_with_strip(s_layout, &optimizer.instance, &optimizer.mirrored_items, strip, config.svg_draw_options);
+        io::try_write_svg(&svg, &svg_path)?;
+    }
+    Ok(())
 }
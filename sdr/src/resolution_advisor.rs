@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use itertools::Itertools;
+use jagua_rs::entities::instances::instance::Instance;
+use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
+use jagua_rs::fsize;
+use jagua_rs::geometry::geo_traits::{Shape, Transformable};
+use jagua_rs::geometry::transformation::Transformation;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::discrete_item::Discretizable;
+use crate::sdr_config::{SDRConfig, Validation};
+use crate::sdr_error::SdrError;
+use crate::sdr_optimizer::SDROptimizer;
+use crate::sdr_parse::SdrInstance;
+
+/// An item spans at least this many lines in every orientation at the advised resolution
+const MIN_LINES_PER_ITEM: fsize = 2.0;
+
+/// Lines over the estimated width of the strip at which the first solve is timed, for a `ResolutionTarget::TimeBudget`
+const PROBE_LINES: usize = 32;
+
+/// The lines of the probes are doubled until one takes this fraction of the time budget
+const PROBE_BUDGET_FRACTION: f64 = 0.25;
+
+/// Lines over the estimated width of the strip at most for a `ResolutionTarget::TimeBudget`,
+/// finer resolutions hardly reduce the over approximation anymore
+const MAX_BUDGET_LINES: usize = 4096;
+
+/// What the resolution is chosen for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResolutionTarget {
+    /// About this many lines over the estimated width of the strip, or over the widest bin
+    Lines(usize),
+    /// A plain `SDROptimizer::solve`, discretization included, takes about this long.
+    /// The time is extrapolated from solves at coarser resolutions, which double the lines until one of them
+    /// takes a quarter of the budget, so the advice itself takes about half of it.
+    TimeBudget(Duration),
+}
+
+/// Resolution advised for an instance, with the geometry it was derived from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResolutionAdvice {
+    pub resolution: fsize,
+    /// Smallest width of an item over its orientations
+    pub min_item_width: fsize,
+    /// Median length of the edges of the shapes of the items
+    pub median_edge_length: fsize,
+    /// Height of the strip, or of the tallest bin
+    pub height: fsize,
+    /// Width of a strip holding the area of all items without waste, or of the widest bin
+    pub width_estimate: fsize,
+    /// Lines over `width_estimate` at the advised resolution
+    pub lines: usize,
+    /// Area covered by the discrete lines of all copies of the items beyond their true area,
+    /// in the first orientation of every item
+    pub over_approximation: fsize,
+    /// `over_approximation` as a fraction of the true area of the items
+    pub over_approximation_ratio: fsize,
+    /// Time extrapolated for a solve at the advised resolution, for a `ResolutionTarget::TimeBudget`
+    pub expected_secs: Option<f64>,
+}
+
+/// Advises a resolution for an instance from the geometry of its items. The resolution follows the target,
+/// but is never coarser than half the narrowest item nor than the median edge, so small items and the outlines
+/// of the shapes are not lost.
+/// The lines of the instance are not used, it may be discretized at any resolution or not at all.
+pub fn advise_resolution(instance: &SdrInstance, config: &SDRConfig, target: ResolutionTarget) -> ResolutionAdvice {
+    try_advise_resolution(instance, config, target).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Same as `advise_resolution`, but fails instead of panicking on an instance without items
+pub fn try_advise_resolution(instance: &SdrInstance, config: &SDRConfig, target: ResolutionTarget) -> Result<ResolutionAdvice, SdrError> {
+    let items = instance.instance.items();
+    let min_item_width = items
+        .iter()
+        .flat_map(|(item, _)| {
+            instance.rotations[&item.id]
+                .iter()
+                .map(|rotation| item.shape.transform_clone(&Transformation::from_rotation(*rotation)).bbox().width())
+        })
+        .min_by_key(|width| OrderedFloat(*width))
+        .ok_or_else(|| SdrError::InvalidInstance("a resolution cannot be advised without items".to_string()))?;
+    let edge_lengths = items
+        .iter()
+        .flat_map(|(item, _)| item.shape.edge_iter().map(|edge| edge.diameter()))
+        .sorted_by_key(|length| OrderedFloat(*length))
+        .collect_vec();
+    let median_edge_length = edge_lengths[edge_lengths.len() / 2];
+    let item_area = items.iter().map(|(item, qty)| instance.material_area(item.id) * *qty as fsize).sum::<fsize>();
+    let (height, width_estimate) = match &instance.instance {
+        Instance::SP(sp_instance) => (sp_instance.strip_height, item_area / sp_instance.strip_height),
+        Instance::BP(bp_instance) => {
+            let bboxes = bp_instance.bins.iter().map(|(bin, _)| bin.outer.bbox()).collect_vec();
+            let height = bboxes.iter().map(|bbox| bbox.height()).fold(0.0, fsize::max);
+            let width = bboxes.iter().map(|bbox| bbox.width()).fold(0.0, fsize::max);
+            (height, width)
+        }
+    };
+    let max_resolution = (min_item_width / MIN_LINES_PER_ITEM).min(median_edge_length);
+
+    let (resolution, expected_secs) = match target {
+        ResolutionTarget::Lines(lines) => ((width_estimate / lines.max(1) as fsize).min(max_resolution), None),
+        ResolutionTarget::TimeBudget(budget) => {
+            let budget_secs = budget.as_secs_f64().max(f64::EPSILON);
+            let probe = |lines: usize| {
+                let resolution = width_estimate / lines as fsize;
+                (resolution, probe_secs(instance, config, resolution))
+            };
+            let mut lines = PROBE_LINES;
+            let (mut coarse, mut coarse_secs) = probe(lines);
+            let (mut fine, mut fine_secs) = probe(2 * lines);
+            while fine_secs < budget_secs * PROBE_BUDGET_FRACTION && 2 * lines < MAX_BUDGET_LINES {
+                lines *= 2;
+                (coarse, coarse_secs) = (fine, fine_secs);
+                (fine, fine_secs) = probe(2 * lines);
+            }
+            // solve time ∝ resolution^-exponent, between a pass over every line and every line of every item
+            let exponent = ((fine_secs / coarse_secs).ln() / (coarse as f64 / fine as f64).ln()).clamp(1.0, 3.0);
+            let resolution = (fine * (fine_secs / budget_secs).powf(1.0 / exponent) as fsize)
+                .max(width_estimate / MAX_BUDGET_LINES as fsize)
+                .min(max_resolution);
+            let expected_secs = fine_secs * (fine as f64 / resolution as f64).powf(exponent);
+            if expected_secs > budget_secs * 1.5 {
+                log::warn!(
+                    "[SDR] resolution {} is capped by the geometry, a solve is expected to take {:.3}s",
+                    resolution,
+                    expected_secs
+                );
+            }
+            (resolution, Some(expected_secs))
+        }
+    };
+
+    let discrete_area = items
+        .iter()
+        .map(|(item, qty)| {
+            let geometry = instance.geometry.get(&item.id).cloned().unwrap_or_default();
+            let lines = item.discretize_geometry(resolution, instance.rotations[&item.id][0], &geometry);
+            let length = lines.iter().flat_map(|line| &line.occupied).map(|interval| interval.end - interval.start).sum::<fsize>();
+            length * resolution * *qty as fsize
+        })
+        .sum::<fsize>();
+    let over_approximation = discrete_area - item_area;
+
+    let advice = ResolutionAdvice {
+        resolution,
+        min_item_width,
+        median_edge_length,
+        height,
+        width_estimate,
+        lines: (width_estimate / resolution).ceil() as usize + 1,
+        over_approximation,
+        over_approximation_ratio: over_approximation / item_area,
+        expected_secs,
+    };
+    log::info!("[SDR] advised resolution {:?}", advice);
+    Ok(advice)
+}
+
+/// Seconds it takes to discretize the instance at `resolution` and solve it once
+fn probe_secs(instance: &SdrInstance, config: &SDRConfig, resolution: fsize) -> f64 {
+    let config = SDRConfig { compaction_sweeps: 0, validation: Validation::Off, time_limit: None, ..*config };
+    let mut optimizer = SDROptimizer::new(instance.rediscretize(resolution), config, resolution);
+    optimizer.solve();
+    (optimizer.report.discretization_secs + optimizer.report.total_secs).max(f64::EPSILON)
+}
@@ -14,13 +14,162 @@ pub struct SDRConfig {
     pub poly_simpl_tolerance: Option<fsize>,
     /// Seed for the PRNG. If undefined, the algorithm will run in non-deterministic mode using entropy
     pub prng_seed: Option<u64>,
-    /// Total budget of samples per item per layout
+    /// Placement orders sampled by `SDROptimizer::random_search` and `minimize_strip` after the order of `solve`,
+    /// each one is a whole strip pass
     pub n_samples: usize,
-    /// Fraction of `n_samples_per_item` used for the local search sampler, the rest is sampled uniformly.
+    /// Fraction of `n_samples` which perturb the best order so far, by swapping two items or changing the orientation
+    /// an item tries first. They follow the others, which are uniformly random orders.
     pub ls_frac: f32,
     /// Optional SVG drawing options
     #[serde(default)]
     pub svg_draw_options: SvgDrawOptions,
+    /// How the bin for each item is chosen in bin packing problems
+    #[serde(default)]
+    pub bin_selection: BinSelection,
+    /// Angles tried for items which can rotate freely
+    #[serde(default)]
+    pub rotation_sampling: RotationSampling,
+    /// Order in which the items are placed by `SDROptimizer::solve` and where the searches start from
+    #[serde(default)]
+    pub item_ordering: ItemOrdering,
+    /// Parameters of `SDROptimizer::genetic_search`
+    #[serde(default)]
+    pub brkga: BrkgaConfig,
+    /// Fraction of the strip width removed at every step of `SDROptimizer::minimize_strip`
+    #[serde(default = "default_shrink_step")]
+    pub shrink_step: fsize,
+    /// Time limit in seconds of the searches of `SDROptimizer`, they return their best solution when it runs out.
+    /// If undefined, they run their full budget and `minimize_strip` runs until the strip cannot shrink anymore
+    #[serde(default)]
+    pub time_limit: Option<fsize>,
+    /// Sweeps of the compaction which slides the items of the final solution of a strip packing problem left and down
+    /// with exact collision checks, to recover the space lost to the resolution. No compaction if 0
+    #[serde(default)]
+    pub compaction_sweeps: usize,
+    /// Check of the final solution against the exact shapes, with the collision detection engine of jagua-rs
+    #[serde(default)]
+    pub validation: Validation,
+    /// Penetration depth up to which a collision found by the validation is ignored, in the units of the instance
+    #[serde(default = "default_validation_tolerance")]
+    pub validation_tolerance: fsize,
+    /// Minimum gap between two items, e.g. the kerf of the cut. The discretized items are inflated by half of it,
+    /// so an item also keeps half of it from the defects and from the border of a bin
+    #[serde(default)]
+    pub item_spacing: fsize,
+    /// Minimum gap between the items and the bottom, the top and the start of the strip, for strip packing problems.
+    /// The compaction is skipped if there is a spacing or a margin
+    #[serde(default)]
+    pub edge_margin: fsize,
+}
+
+fn default_shrink_step() -> fsize {
+    0.01
+}
+
+fn default_validation_tolerance() -> fsize {
+    1e-3
+}
+
+/// What to do with the collisions found by the validation of a solution
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Validation {
+    /// The solution is not validated
+    #[default]
+    Off,
+    /// The collisions are logged and listed in the `SolveReport`
+    Report,
+    /// As `Report`, and the solve fails if there is any collision
+    Strict,
+}
+
+/// Strategy to select an opened bin for an item in bin packing problems
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinSelection {
+    /// The first opened bin in which the item fits
+    #[default]
+    FirstFit,
+    /// The opened bin with the least free space left in which the item fits
+    BestFit,
+}
+
+/// Strategy to sample the angles of items with `AllowedRotation::Continuous`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RotationSampling {
+    /// `n` evenly spaced angles, starting from 0
+    Uniform(usize),
+    /// The principal axes of the shape aligned with the axes of the strip
+    PrincipalAxes,
+    /// The longest edge of the convex hull aligned with the strip axis, at the top or the bottom
+    LongestHullEdge,
+}
+
+/// Parameters of the biased random-key genetic algorithm
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BrkgaConfig {
+    /// Number of chromosomes in every generation
+    pub population_size: usize,
+    /// Maximum number of generations
+    pub n_generations: usize,
+    /// Fraction of the population kept as is in the next generation
+    pub elite_frac: f32,
+    /// Fraction of the population replaced by random chromosomes in every generation
+    pub mutant_frac: f32,
+    /// Probability that an offspring inherits a key from its elite parent
+    pub elite_bias: f32,
+}
+
+impl Default for BrkgaConfig {
+    fn default() -> Self {
+        Self {
+            population_size: 40,
+            n_generations: 50,
+            elite_frac: 0.2,
+            mutant_frac: 0.15,
+            elite_bias: 0.7,
+        }
+    }
+}
+
+/// Heuristic to order the items before they are placed, all sort in descending order
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemOrdering {
+    /// Diameter of the convex hull
+    #[default]
+    HullDiameter,
+    /// Area of the shape
+    Area,
+    /// Width of the bounding box
+    BboxWidth,
+    /// Height of the bounding box
+    BboxHeight,
+    /// Area of the convex hull
+    HullArea,
+    /// Perimeter of the shape
+    Perimeter,
+    /// Random order drawn with the PRNG of `prng_seed`
+    Random,
+    /// Area of the shape times the demand of the item
+    DemandWeighted,
+}
+
+impl ItemOrdering {
+    /// Every built-in ordering
+    pub const ALL: [ItemOrdering; 8] = [
+        ItemOrdering::HullDiameter,
+        ItemOrdering::Area,
+        ItemOrdering::BboxWidth,
+        ItemOrdering::BboxHeight,
+        ItemOrdering::HullArea,
+        ItemOrdering::Perimeter,
+        ItemOrdering::Random,
+        ItemOrdering::DemandWeighted,
+    ];
+}
+
+impl Default for RotationSampling {
+    fn default() -> Self {
+        RotationSampling::Uniform(4)
+    }
 }
 
 impl Default for SDRConfig {
@@ -41,6 +190,17 @@ impl Default for SDRConfig {
             n_samples: 5000,
             ls_frac: 0.0,
             svg_draw_options: SvgDrawOptions::default(),
+            bin_selection: BinSelection::default(),
+            rotation_sampling: RotationSampling::default(),
+            item_ordering: ItemOrdering::default(),
+            brkga: BrkgaConfig::default(),
+            shrink_step: default_shrink_step(),
+            time_limit: None,
+            compaction_sweeps: 0,
+            validation: Validation::default(),
+            validation_tolerance: default_validation_tolerance(),
+            item_spacing: 0.0,
+            edge_margin: 0.0,
         }
     }
 }
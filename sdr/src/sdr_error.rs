@@ -0,0 +1,72 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use jagua_rs::fsize;
+
+use crate::validation::Violation;
+
+/// Errors of the fallible entry points, for callers which cannot afford a panic on bad input
+#[derive(Debug)]
+pub enum SdrError {
+    /// A file could not be opened, created or written
+    Io { path: PathBuf, source: std::io::Error },
+    /// A file does not hold valid JSON of the expected format
+    Json { path: PathBuf, source: serde_json::Error },
+    /// The instance does not specify exactly one of bins or a strip
+    InvalidInstance(String),
+    InvalidItem { item_id: usize, reason: String },
+    InvalidBin { bin_id: usize, reason: String },
+    /// The strip, its defects or its quality zones are invalid
+    InvalidStrip(String),
+    /// The distance between the discrete lines is not strictly positive
+    InvalidResolution(fsize),
+    /// The time limit of the searches is negative, not a number or too large
+    InvalidTimeLimit(fsize),
+    /// None of the orientations of an item fits in the strip or in any bin
+    ItemDoesNotFit { item_id: usize, reason: String },
+    /// A color is not of the form `#RRGGBB`
+    InvalidColor(String),
+    /// The validation in `Validation::Strict` mode found collisions in the solution
+    InfeasibleSolution(Vec<Violation>),
+}
+
+impl Display for SdrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SdrError::Io { path, source } => write!(f, "could not access file {}: {}", path.display(), source),
+            SdrError::Json { path, source } => write!(f, "invalid JSON in file {}: {}", path.display(), source),
+            SdrError::InvalidInstance(reason) => write!(f, "invalid instance: {}", reason),
+            SdrError::InvalidItem { item_id, reason } => write!(f, "invalid item {}: {}", item_id, reason),
+            SdrError::InvalidBin { bin_id, reason } => write!(f, "invalid bin {}: {}", bin_id, reason),
+            SdrError::InvalidStrip(reason) => write!(f, "invalid strip: {}", reason),
+            SdrError::InvalidResolution(resolution) => write!(f, "invalid resolution {}, it has to be strictly positive", resolution),
+            SdrError::InvalidTimeLimit(time_limit) => write!(f, "invalid time limit {}, it has to be a finite number of seconds >= 0", time_limit),
+            SdrError::ItemDoesNotFit { item_id, reason } => write!(f, "item {} does not fit: {}", item_id, reason),
+            SdrError::InvalidColor(color) => write!(f, "invalid color {:?}, expected #RRGGBB", color),
+            SdrError::InfeasibleSolution(violations) => {
+                let deepest = violations.iter().max_by(|a, b| a.penetration_depth.total_cmp(&b.penetration_depth));
+                match deepest {
+                    Some(v) => write!(
+                        f,
+                        "infeasible solution with {} collisions, the deepest is item {} ({:?}) by {}",
+                        violations.len(),
+                        v.item_id,
+                        v.kind,
+                        v.penetration_depth
+                    ),
+                    None => write!(f, "infeasible solution"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for SdrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdrError::Io { source, .. } => Some(source),
+            SdrError::Json { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
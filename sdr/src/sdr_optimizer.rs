@@ -1,205 +1,1221 @@
 use std::cmp::Reverse;
-use std::time::Instant;
+use std::collections::HashSet;
+use std::iter;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use itertools::Itertools;
 use jagua_rs::fsize;
 use jagua_rs::geometry::d_transformation::DTransformation;
-use jagua_rs::geometry::geo_enums::AllowedRotation;
 use ordered_float::NotNan;
+use rand::prelude::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
 
 
+use jagua_rs::entities::bin::Bin;
 use jagua_rs::entities::instances::instance::Instance;
 use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
 use jagua_rs::entities::item::Item;
+use jagua_rs::entities::placed_item::PItemKey;
 use jagua_rs::entities::placing_option::PlacingOption;
 
+use jagua_rs::entities::problems::bin_packing::BPProblem;
 use jagua_rs::entities::problems::problem::Problem;
-use jagua_rs::entities::problems::problem_generic::{ProblemGeneric, STRIP_LAYOUT_IDX};
+use jagua_rs::entities::problems::problem_generic::{LayoutIndex, ProblemGeneric, STRIP_LAYOUT_IDX};
 use jagua_rs::entities::problems::strip_packing::SPProblem;
 use jagua_rs::entities::solution::Solution;
 use jagua_rs::geometry::convex_hull::convex_hull_from_points;
 use jagua_rs::geometry::geo_traits::Shape;
+use jagua_rs::geometry::primitives::aa_rectangle::AARectangle;
 use jagua_rs::geometry::primitives::simple_polygon::SimplePolygon;
 
 
-use crate::discrete_item::Discretizable;
+use crate::brkga::Brkga;
+use crate::discrete_item::{mirror_polygon, spacing_lines, Discretizable, ItemComponent};
 
-use crate::discrete_line::{get_overlaps_count, DiscreteLine, DiscreteStrip};
-use crate::sdr_config::SDRConfig;
+use crate::discrete_line::{DiscreteLine, DiscreteStrip};
+use crate::io::layout_to_svg::StripOverlay;
+use crate::sdr_config::{BinSelection, ItemOrdering, SDRConfig, Validation};
+use crate::sdr_error::SdrError;
 use crate::sdr_parse::{ItemDiscrete, SdrInstance};
+use crate::solve_report::{SolveReport, UnplacedItem, UnplacedReason};
+use crate::terminator::{CancellationToken, Terminator};
+use crate::compaction;
+use crate::validation::{validate_layout, ExactLayout, Violation};
 
 
 pub const ITEM_LIMIT: usize = usize::MAX;
 
+/// Called with every improved solution found by a search
+pub type SolutionListener = Box<dyn FnMut(&Solution) + Send>;
+
+/// Placements of a pass in order, with whether the item is mirrored
+type Placements = Vec<(PlacingOption, bool)>;
+/// Number of missing copies per item id, for the items which did not fully fit
+type MissingCopies = Vec<(usize, usize)>;
+
+/// Copy of an item placed by a strip pass, in its orientation and at the distance of its first line from the start
+/// of the discrete frame. The finer passes of `solve_coarse_to_fine` start from it.
+#[derive(Debug, Clone, Copy)]
+struct PlacedCopy {
+    item_discrete: ItemDiscrete,
+    x: fsize,
+}
+
 pub struct SDROptimizer{
     pub instance: SdrInstance,
     pub problem: Problem,
     pub config: SDRConfig,
     pub resolution: fsize,
     pub discrete_strip: DiscreteStrip,
+    /// Bins opened so far, only used for bin packing problems
+    pub discrete_bins: Vec<DiscreteBin>,
     pub transforms: Vec<DTransformation>,
+    /// Placed items which are mirrored, by the id of their layout and their key.
+    /// jagua-rs only knows rigid transformations, the layouts hold them unmirrored.
+    pub mirrored_items: HashSet<(usize, PItemKey)>,
+    /// Stops the searches at the deadline of `time_limit` or when they are cancelled
+    pub terminator: Terminator,
+    /// Called with every improved solution, as soon as it is found
+    solution_listener: Option<SolutionListener>,
+    /// Statistics of the last solve or search
+    pub report: SolveReport,
+}
+
+/// A bin opened during bin packing, together with its own discretized strip
+#[derive(Debug, Clone)]
+pub struct DiscreteBin {
+    /// Layout of the bin in the problem, `LayoutIndex::Template` until the first item is placed
+    pub layout_idx: LayoutIndex,
+    pub bbox: AARectangle,
+    pub discrete_strip: DiscreteStrip,
+}
+
+impl DiscreteBin {
+    pub fn new(layout_idx: LayoutIndex, bin: &Bin, resolution: fsize) -> Self {
+        let bbox = bin.bbox();
+        // Lines are placed on the bbox, the last one cannot lie outside of it
+        let num_lines = (bbox.width() / resolution).floor() as usize + 1;
+        let mut discrete_strip = DiscreteStrip::new(vec![DiscreteLine::new(); num_lines]);
+
+        // The exterior of the bin is a frame around its bbox with the bin as hole, the frame is
+        // larger than the bbox so its own corners do not end up on the lines
+        let frame = AARectangle::new(
+            bbox.x_min - resolution,
+            bbox.y_min - resolution,
+            bbox.x_max + resolution,
+            bbox.y_max + resolution,
+        );
+        let exterior = ItemComponent {
+            outer: SimplePolygon::from(frame),
+            holes: vec![bin.outer.as_ref().clone()],
+        };
+        let obstacles = iter::once(exterior)
+            .chain(bin.holes.iter().map(|hole| ItemComponent { outer: hole.as_ref().clone(), holes: vec![] }))
+            .collect_vec();
+        discrete_strip.add_obstacles(&obstacles, &bbox, resolution);
+        let quality_zones = bin.quality_zones.iter().flatten().cloned().collect_vec();
+        discrete_strip.add_quality_zones(&quality_zones, &bbox, resolution);
+
+        Self { layout_idx, bbox, discrete_strip }
+    }
+}
+
+/// Order in which a strip pass places the items, together with the orientation every item tries first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementOrder {
+    /// Ids of the items, in the order in which they are placed
+    pub items: Vec<usize>,
+    /// Index in `SdrInstance::orientations` of the orientation tried first, per item id.
+    /// Orientations are only preferred when they are as good as the others.
+    pub first_orientations: Vec<usize>,
+}
+
+impl PlacementOrder {
+    pub fn new(items: Vec<usize>) -> Self {
+        let first_orientations = vec![0; items.len()];
+        Self { items, first_orientations }
+    }
+
+    /// Uniformly random order and first orientations
+    pub fn random(n_orientations: &[usize], rng: &mut impl Rng) -> Self {
+        let mut items = (0..n_orientations.len()).collect_vec();
+        items.shuffle(rng);
+        let first_orientations = n_orientations.iter().map(|n| rng.random_range(0..(*n).max(1))).collect();
+        Self { items, first_orientations }
+    }
+
+    /// Swaps two random items, or changes the first orientation of a random item
+    pub fn perturbed(&self, n_orientations: &[usize], rng: &mut impl Rng) -> Self {
+        let mut perturbed = self.clone();
+        let n_items = self.items.len();
+        if n_items == 0 {
+            return perturbed;
+        }
+        match rng.random_bool(0.5) {
+            true => perturbed.items.swap(rng.random_range(0..n_items), rng.random_range(0..n_items)),
+            false => {
+                let item_id = rng.random_range(0..n_items);
+                perturbed.first_orientations[item_id] = rng.random_range(0..n_orientations[item_id].max(1));
+            }
+        }
+        perturbed
+    }
+
+    /// The order with every item trying its next orientation first, followed by every order with two
+    /// consecutive items swapped, each with the same and the next orientations first
+    pub fn neighbours(&self) -> impl Iterator<Item = PlacementOrder> + '_ {
+        let next_orientations = self.first_orientations.iter().map(|o| o + 1).collect_vec();
+        let swapped = (0..self.items.len().saturating_sub(1)).flat_map(move |i| {
+            let mut items = self.items.clone();
+            items.swap(i, i + 1);
+            [
+                PlacementOrder { items: items.clone(), first_orientations: self.first_orientations.clone() },
+                PlacementOrder { items, first_orientations: self.first_orientations.iter().map(|o| o + 1).collect() },
+            ]
+        });
+        iter::once(PlacementOrder { items: self.items.clone(), first_orientations: next_orientations }).chain(swapped)
+    }
 }
 
 impl SDROptimizer {
     pub fn new(instance: SdrInstance, config: SDRConfig, resolution: fsize) -> Self{
-        let problem = match instance.instance.clone() {
+        let problem: Problem = match instance.instance.clone() {
             Instance::SP(spi) => {
-                let strip_width = instance.instance.item_area() * 2.0 / spi.strip_height; //initiate with 50% usage            
+                let material_area = (0..instance.instance.items().len())
+                    .map(|item_id| instance.material_area(item_id) * instance.instance.item_qty(item_id) as fsize)
+                    .sum::<fsize>();
+                let strip_width = material_area * 2.0 / spi.strip_height; //initiate with 50% usage
                 SPProblem::new(spi.clone(), strip_width, config.cde_config).into()
             }
-            _ => panic!("SDROptimizer only supports Strip Packing problems (SPProblem)")
+            Instance::BP(bpi) => BPProblem::new(bpi).into(),
         };
-        let width = match &problem {
-            Problem::SP(sp_problem) => sp_problem.strip_width(),
-            _ => panic!("SDROptimizer only supports Strip Packing problems")
+        let discrete_strip = match &problem {
+            Problem::SP(_) => Self::growing_discrete_strip(&problem, &instance, resolution),
+            // every opened bin gets its own strip
+            Problem::BP(_) => DiscreteStrip::new(Vec::new()),
         };
-        let discrete_strip = DiscreteStrip { lines: Self::generate_discrete_lines(width, resolution)};
         let transforms = Vec::new();
-        
-        Self { instance, problem, config, resolution, discrete_strip, transforms}
+        let report = SolveReport::new(instance.discretization_time);
+
+        Self { instance, problem, config, resolution, discrete_strip, discrete_bins: Vec::new(), transforms, mirrored_items: HashSet::new(), terminator: Terminator::default(), solution_listener: None, report }
     }
 
-    pub fn generate_discrete_lines(strip_width: fsize, resolution: fsize) -> Vec<DiscreteLine> {
-        let num_lines = (strip_width / resolution).ceil() as u32;
-        
-        (0..num_lines)
-            .map(|_i| {
-                DiscreteLine::new()
+    /// Checks that the resolution is strictly positive, that the time limit is a valid duration and that every item
+    /// fits in the strip or in some bin in at least one of its orientations, before creating the optimizer
+    pub fn try_new(instance: SdrInstance, config: SDRConfig, resolution: fsize) -> Result<Self, SdrError> {
+        if !(resolution.is_finite() && resolution > 0.0) {
+            return Err(SdrError::InvalidResolution(resolution));
+        }
+        if let Some(time_limit) = config.time_limit {
+            Duration::try_from_secs_f32(time_limit).map_err(|_| SdrError::InvalidTimeLimit(time_limit))?;
+        }
+        for item_id in 0..instance.instance.items().len() {
+            let bboxes = instance.orientation_bboxes(item_id);
+            match &instance.instance {
+                Instance::SP(spi) => {
+                    let min_height = bboxes.iter().map(|bbox| bbox.height()).fold(fsize::INFINITY, fsize::min);
+                    let usable_height = spi.strip_height - 2.0 * instance.edge_margin;
+                    if min_height > usable_height {
+                        return Err(SdrError::ItemDoesNotFit {
+                            item_id,
+                            reason: format!("its lowest orientation is {} high, the strip only {} within its margins", min_height, usable_height),
+                        });
+                    }
+                }
+                Instance::BP(bpi) => {
+                    let fits = bpi.bins.iter().any(|(bin, _)| {
+                        let bin_bbox = bin.bbox();
+                        bboxes.iter().any(|bbox| bbox.width() <= bin_bbox.width() && bbox.height() <= bin_bbox.height())
+                    });
+                    if !fits {
+                        return Err(SdrError::ItemDoesNotFit { item_id, reason: "it is larger than every bin".to_string() });
+                    }
+                }
+            }
+        }
+        Ok(Self::new(instance, config, resolution))
+    }
+
+    /// Discrete strip of a strip packing problem with `num_lines` lines, holding the defects and quality zones of the strip
+    fn new_discrete_strip(problem: &Problem, instance: &SdrInstance, num_lines: usize, resolution: fsize) -> DiscreteStrip {
+        let mut discrete_strip = DiscreteStrip::new(vec![DiscreteLine::new(); num_lines]);
+        Self::add_strip_features(&mut discrete_strip, problem, instance, resolution);
+        discrete_strip
+    }
+
+    /// Discrete strip of a strip packing problem which only holds the defects and quality zones of the strip at first.
+    /// It grows as far as the items need it, up to a strip on which all items fit side by side after them.
+    fn growing_discrete_strip(problem: &Problem, instance: &SdrInstance, resolution: fsize) -> DiscreteStrip {
+        let bbox = Self::discrete_frame(problem, instance, STRIP_LAYOUT_IDX, resolution);
+        let features_x_max = instance
+            .strip_defects
+            .iter()
+            .map(|defect| defect.outer.bbox().x_max)
+            .chain(instance.strip_quality_zones.iter().flat_map(|qz| qz.zones.iter().map(|zone| zone.bbox().x_max)))
+            .fold(bbox.x_min, fsize::max);
+        let features_lines = ((features_x_max - bbox.x_min) / resolution).ceil() as usize + 1;
+        let items_width = (0..instance.instance.items().len())
+            .map(|item_id| {
+                let widest = instance.orientation_bboxes(item_id).iter().map(|bbox| bbox.width()).fold(0.0, fsize::max);
+                widest * instance.instance.item_qty(item_id) as fsize
             })
-            .collect()
+            .sum::<fsize>();
+        let max_lines = features_lines + (items_width / resolution).ceil() as usize + 1;
+
+        let mut discrete_strip = DiscreteStrip::growing(vec![DiscreteLine::new(); features_lines], max_lines);
+        Self::add_strip_features(&mut discrete_strip, problem, instance, resolution);
+        discrete_strip
+    }
+
+    fn add_strip_features(discrete_strip: &mut DiscreteStrip, problem: &Problem, instance: &SdrInstance, resolution: fsize) {
+        let frame = Self::discrete_frame(problem, instance, STRIP_LAYOUT_IDX, resolution);
+        discrete_strip.add_obstacles(&instance.strip_defects, &frame, resolution);
+        discrete_strip.add_quality_zones(&instance.strip_quality_zones, &frame, resolution);
+    }
+
+    /// Rectangle spanned by the discrete strip of a layout, the bbox of its bin for bin packing problems.
+    /// The frame of a strip is inset by `edge_margin` at the bottom, the top and the start, but the dilated lines
+    /// of the items reach half of `item_spacing` beyond their shape, so it is grown again by that much.
+    fn discrete_frame(problem: &Problem, instance: &SdrInstance, layout_idx: LayoutIndex, resolution: fsize) -> AARectangle {
+        let bbox = problem.get_layout(layout_idx).bin.bbox();
+        match problem {
+            Problem::SP(_) => {
+                let (spacing_x, spacing_y) = Self::spacing_offset(instance, resolution);
+                let x_min = bbox.x_min + instance.edge_margin - spacing_x;
+                let y_inset = instance.edge_margin - spacing_y;
+                AARectangle::new(x_min, bbox.y_min + y_inset, bbox.x_max.max(x_min + resolution), bbox.y_max - y_inset)
+            }
+            Problem::BP(_) => bbox,
+        }
+    }
+
+    /// Discretized strip on which the items of a layout of the last solution were placed, by the id of the layout,
+    /// to draw it with `sdr_layout_to_svg_with_strip`. A compacted strip no longer matches it.
+    pub fn strip_overlay(&self, layout_id: usize) -> Option<StripOverlay<'_>> {
+        match &self.problem {
+            Problem::SP(_) => (self.problem.get_layout(STRIP_LAYOUT_IDX).id() == layout_id).then(|| StripOverlay {
+                discrete_strip: &self.discrete_strip,
+                frame: Self::discrete_frame(&self.problem, &self.instance, STRIP_LAYOUT_IDX, self.resolution),
+                resolution: self.resolution,
+            }),
+            Problem::BP(_) => self
+                .discrete_bins
+                .iter()
+                .find(|d_bin| matches!(d_bin.layout_idx, LayoutIndex::Real(_)) && self.problem.get_layout(d_bin.layout_idx).id() == layout_id)
+                .map(|d_bin| StripOverlay { discrete_strip: &d_bin.discrete_strip, frame: d_bin.bbox.clone(), resolution: self.resolution }),
+        }
+    }
+
+    /// Offset of the shape of an item from the start and the top of its dilated lines, see `dilate_lines`
+    fn spacing_offset(instance: &SdrInstance, resolution: fsize) -> (fsize, fsize) {
+        let half_spacing = instance.item_spacing / 2.0;
+        (spacing_lines(half_spacing, resolution) as fsize * resolution, half_spacing)
     }
 
     pub fn solve(&mut self) -> Solution {
-        //sort the items by descending diameter of convex hull 
-        let mut opts: Vec<PlacingOption> = Vec::new();
-        let sorted_item_indices = (0..self.instance.instance.items().len())
+        self.try_solve().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `solve`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_solve(&mut self) -> Result<Solution, SdrError> {
+        let start_time = self.start_report();
+        let sorted_item_indices = self.item_order(self.config.item_ordering);
+
+        let solution = match &self.problem {
+            Problem::SP(_) => self.solve_strip(sorted_item_indices),
+            Problem::BP(_) => self.solve_bins(sorted_item_indices),
+        };
+        self.finish_solve(solution, start_time)
+    }
+
+    /// Indices of the items in the order of an `ItemOrdering`
+    pub fn item_order(&self, ordering: ItemOrdering) -> Vec<usize> {
+        let items = self.instance.instance.items();
+        if ordering == ItemOrdering::Random {
+            let mut order = (0..items.len()).collect_vec();
+            order.shuffle(&mut self.rng());
+            return order;
+        }
+        (0..items.len())
             .sorted_by_cached_key(|i| {
-                let item = &self.instance.instance.items()[*i].0;
-                let ch = SimplePolygon::new(convex_hull_from_points(item.shape.points.clone()));
-                let ch_diam = NotNan::new(ch.diameter()).expect("convex hull diameter is NaN");
-                Reverse(ch_diam)
+                let (item, demand) = &items[*i];
+                let shape = item.shape.as_ref();
+                let key = match ordering {
+                    ItemOrdering::HullDiameter => SimplePolygon::new(convex_hull_from_points(shape.points.clone())).diameter(),
+                    ItemOrdering::Area => shape.area(),
+                    ItemOrdering::BboxWidth => shape.bbox().width(),
+                    ItemOrdering::BboxHeight => shape.bbox().height(),
+                    ItemOrdering::HullArea => SimplePolygon::new(convex_hull_from_points(shape.points.clone())).area(),
+                    ItemOrdering::Perimeter => shape.edge_iter().map(|e| (e.end.0 - e.start.0).hypot(e.end.1 - e.start.1)).sum(),
+                    ItemOrdering::DemandWeighted => shape.area() * *demand as fsize,
+                    ItemOrdering::Random => unreachable!(),
+                };
+                Reverse(NotNan::new(key).expect("ordering key is NaN"))
+            })
+            .collect_vec()
+    }
+
+    /// PRNG seeded with `prng_seed`, or with entropy if it is undefined
+    fn rng(&self) -> SmallRng {
+        match self.config.prng_seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_os_rng(),
+        }
+    }
+
+    /// Solves a strip packing problem with the biased random-key genetic algorithm of `brkga`, decoding every
+    /// chromosome with a strip pass. The population starts from the order of `solve`, `time_limit` is respected
+    /// between generations. Bin packing problems are only solved once.
+    pub fn genetic_search(&mut self) -> Solution {
+        self.try_genetic_search().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `genetic_search`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_genetic_search(&mut self) -> Result<Solution, SdrError> {
+        if let Problem::BP(_) = &self.problem {
+            return self.try_solve();
+        }
+        let start_time = self.start_report();
+        self.start_terminator();
+        let n_orientations = (0..self.instance.instance.items().len())
+            .map(|item_id| self.instance.orientations(item_id).len())
+            .collect_vec();
+        let seed = PlacementOrder::new(self.item_order(self.config.item_ordering));
+        // all chromosomes are decoded on a strip as wide as the one the seed grew
+        let (seed_strip, _, _) = Self::growing_strip_pass(&self.problem, &self.instance, self.resolution, &seed);
+        self.report.add_pass(&seed_strip.counters);
+        let num_lines = seed_strip.lines.len();
+
+        let mut rng = self.rng();
+        let resolution = self.resolution;
+        let decode = |problem: &Problem, instance: &SdrInstance, order: &PlacementOrder| {
+            let mut discrete_strip = Self::new_discrete_strip(problem, instance, num_lines, resolution);
+            let (placements, missing) = Self::strip_pass(problem, instance, &resolution, &mut discrete_strip, order);
+            discrete_strip.trim_after_last_occupied();
+            (discrete_strip, placements, missing)
+        };
+        // the chromosomes are evaluated in parallel
+        let evaluations = Mutex::new(SolveReport::default());
+        let fitness = |problem: &Problem, instance: &SdrInstance, order: &PlacementOrder| {
+            let (discrete_strip, _, missing) = decode(problem, instance, order);
+            evaluations.lock().unwrap().add_pass(&discrete_strip.counters);
+            (missing.iter().map(|(_, copies)| copies).sum(), discrete_strip.lines.len())
+        };
+
+        let mut brkga = Brkga::new(self.config.brkga, n_orientations, &seed, &mut rng, |order| fitness(&self.problem, &self.instance, order));
+        let mut improved = true;
+        let mut best_solution = None;
+        for generation in 0..=self.config.brkga.n_generations {
+            if improved {
+                // every improvement is committed to the problem, so it can be reported
+                let (discrete_strip, placements, missing) = decode(&self.problem, &self.instance, &brkga.best().0);
+                self.report.add_pass(&discrete_strip.counters);
+                self.discrete_strip = discrete_strip;
+                best_solution = Some(self.commit_strip_pass(placements, &missing));
+            }
+            if generation == self.config.brkga.n_generations || self.terminator.should_stop() {
+                break;
+            }
+            improved = brkga.step(&mut rng, |order| fitness(&self.problem, &self.instance, order));
+            log::debug!("[BRKGA] generation {}, best fitness {:?}", generation, brkga.best().1);
+        }
+        log::info!("[SDR] genetic search took {:?}, best fitness {:?}", start_time.elapsed(), brkga.best().1);
+
+        self.report.merge(&evaluations.into_inner().unwrap());
+        let solution = best_solution.expect("the initial population is always committed");
+        self.finish_solve(solution, start_time)
+    }
+
+    /// Places the items of a strip packing problem once for every ordering, in parallel and each on its own strip.
+    /// Keeps the narrowest strip in which all items fit, or the first one if none does.
+    /// Once `time_limit` runs out, the orderings not started yet are skipped.
+    /// Bin packing problems are solved once with `item_ordering`.
+    pub fn solve_portfolio(&mut self, orderings: &[ItemOrdering]) -> Solution {
+        self.try_solve_portfolio(orderings).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `solve_portfolio`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_solve_portfolio(&mut self, orderings: &[ItemOrdering]) -> Result<Solution, SdrError> {
+        if let Problem::BP(_) = &self.problem {
+            return self.try_solve();
+        }
+        let start_time = self.start_report();
+        self.start_terminator();
+        let orders = orderings.iter().map(|ordering| PlacementOrder::new(self.item_order(*ordering))).collect_vec();
+        let Some((best_index, discrete_strip, placements, missing)) =
+            Self::best_growing_pass(&self.problem, &self.instance, self.resolution, &orders, &self.terminator, &mut self.report)
+        else {
+            return self.try_solve();
+        };
+        log::info!(
+            "[SDR] portfolio of {} orderings took {:?}, best is {:?}",
+            orderings.len(),
+            start_time.elapsed(),
+            orderings[best_index]
+        );
+        self.discrete_strip = discrete_strip;
+        let solution = self.commit_strip_pass(placements, &missing);
+        self.finish_solve(solution, start_time)
+    }
+
+    /// Runs a growing strip pass for every order in parallel, each on its own strip, and adds them to the report.
+    /// Returns the index of the narrowest trimmed strip in which all items fit, or of the first one if none does,
+    /// together with its pass. Once `terminator` stops, the orders not started yet are skipped.
+    fn best_growing_pass(
+        problem: &Problem,
+        instance: &SdrInstance,
+        resolution: fsize,
+        orders: &[PlacementOrder],
+        terminator: &Terminator,
+        report: &mut SolveReport,
+    ) -> Option<(usize, DiscreteStrip, Placements, MissingCopies)> {
+        let passes = Self::ranked_growing_passes(problem, instance, resolution, orders, terminator, report);
+        let (best_index, (discrete_strip, placements, missing)) = passes.into_iter().next()?;
+        Some((best_index, discrete_strip, placements, missing))
+    }
+
+    /// Runs a growing strip pass for every order in parallel, each on its own strip, and adds them to the report.
+    /// Returns the passes with the index of their order, the passes in which all items fit first and then by the
+    /// width of their trimmed strip. The sort is stable, equal passes keep the order of `orders`.
+    /// Once `terminator` stops, the orders not started yet are skipped, the first one is always run.
+    fn ranked_growing_passes(
+        problem: &Problem,
+        instance: &SdrInstance,
+        resolution: fsize,
+        orders: &[PlacementOrder],
+        terminator: &Terminator,
+        report: &mut SolveReport,
+    ) -> Vec<(usize, (DiscreteStrip, Placements, MissingCopies))> {
+        let passes = orders
+            .par_iter()
+            .enumerate()
+            .filter(|(index, _)| *index == 0 || !terminator.should_stop())
+            .map(|(index, order)| {
+                let (mut discrete_strip, placements, missing) = Self::growing_strip_pass(problem, instance, resolution, order);
+                discrete_strip.trim_after_last_occupied();
+                (index, (discrete_strip, placements, missing))
             })
+            .collect::<Vec<_>>();
+        for (_, (discrete_strip, _, _)) in &passes {
+            report.add_pass(&discrete_strip.counters);
+        }
+        passes
+            .into_iter()
+            .sorted_by_key(|(_, (discrete_strip, _, missing))| (!missing.is_empty(), discrete_strip.lines.len()))
+            .collect()
+    }
+
+    /// Solves a strip packing problem from the coarsest to the finest of the resolutions of `coarse_resolutions`
+    /// which are coarser than `resolution`, and then at `resolution`. At the coarsest one, the orderings of
+    /// `ItemOrdering::ALL` are all tried and the better half of their passes is kept. At every finer resolution, the
+    /// instance is discretized again and the copies of every kept pass are placed again in the same order, each in
+    /// its coarse orientation if it fits as well as the others and searched for from its coarse position. The better
+    /// half is kept again and so on, down to `resolution` where the best pass is kept. Most of the search happens on
+    /// the cheap coarse grids, the fine ones only recover the space lost to the coarse discretization.
+    /// Without coarser resolutions, this is `solve_portfolio` over all orderings. Once `time_limit` runs out, the
+    /// coarser resolutions left are skipped.
+    /// Bin packing problems are only solved once.
+    pub fn solve_coarse_to_fine(&mut self, coarse_resolutions: &[fsize]) -> Solution {
+        self.try_solve_coarse_to_fine(coarse_resolutions).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `solve_coarse_to_fine`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_solve_coarse_to_fine(&mut self, coarse_resolutions: &[fsize]) -> Result<Solution, SdrError> {
+        let levels = coarse_resolutions
+            .iter()
+            .copied()
+            .filter(|resolution| *resolution > self.resolution)
+            .sorted_by(|a, b| b.total_cmp(a))
+            .dedup()
             .collect_vec();
-        //log::info!("sorted {:?}", sorted_item_indices);
+        let Some((&coarsest, finer)) = levels.split_first() else {
+            return self.try_solve_portfolio(&ItemOrdering::ALL);
+        };
+        if let Problem::BP(_) = &self.problem {
+            return self.try_solve();
+        }
+        let start_time = self.start_report();
+        self.start_terminator();
 
-        let start_time = Instant::now();
-        for item_index in &sorted_item_indices {
-            let item = &self.instance.instance.items()[*item_index].0;
-            let missing_items = &self.problem.missing_item_qtys()[*item_index];
-            let mut rotation_lines = Vec::new();         
-                match &item.allowed_rotation {
-                    AllowedRotation::Discrete(angles) => {
-                        for rotation in angles {
-                            if let Some(ref_guard) = self.instance.items.get(&ItemDiscrete { id: *item_index, rotation: ordered_float::OrderedFloat(*rotation) }) {
-                                // Access the actual Vec<DiscreteLine> inside the Ref guard
-                                rotation_lines.push(ref_guard);
-                            }
-                        }
-                    },
-                    _ => {
-                        if let Some(ref_guard) = self.instance.items.get(&ItemDiscrete { id: *item_index, rotation: ordered_float::OrderedFloat(0.0) }) {
-                            // Access the actual Vec<DiscreteLine> inside the Ref guard
-                            rotation_lines.push(ref_guard);
-                        }
-                    },
-                }
-                let placements = Self::discrete_placement(&self.problem, item, &self.resolution, &mut self.discrete_strip, &rotation_lines, missing_items, item_index);
-                for placement in placements{
-                    opts.insert(0, placement);
+        let instance = self.instance.rediscretize(coarsest);
+        self.report.discretization_secs += instance.discretization_time.as_secs_f64();
+        let orders = ItemOrdering::ALL.iter().map(|ordering| PlacementOrder::new(self.item_order(*ordering))).collect_vec();
+        let passes = Self::ranked_growing_passes(&self.problem, &instance, coarsest, &orders, &self.terminator, &mut self.report);
+        let mut candidates = passes
+            .iter()
+            .take(passes.len().div_ceil(2))
+            .map(|(index, (_, placements, missing))| (*index, Self::placed_copies(&self.problem, &instance, coarsest, placements), missing.clone()))
+            .collect_vec();
+        let mut coarser = coarsest;
+        for resolution in finer {
+            // the remaining resolutions are skipped, straight to the fine one
+            if self.terminator.should_stop() {
+                break;
+            }
+            let instance = self.instance.rediscretize(*resolution);
+            self.report.discretization_secs += instance.discretization_time.as_secs_f64();
+            let passes = Self::ranked_refining_passes(&self.problem, &instance, *resolution, &candidates, coarser, &self.terminator, &mut self.report);
+            candidates = passes
+                .iter()
+                .take(passes.len().div_ceil(2))
+                .map(|(index, (_, placements, missing))| (*index, Self::placed_copies(&self.problem, &instance, *resolution, placements), missing.clone()))
+                .collect();
+            log::debug!("[SDR] orderings kept at resolution {}: {:?}", resolution, candidates.iter().map(|(index, _, _)| ItemOrdering::ALL[*index]).collect_vec());
+            coarser = *resolution;
+        }
+
+        let passes = Self::ranked_refining_passes(&self.problem, &self.instance, self.resolution, &candidates, coarser, &self.terminator, &mut self.report);
+        let (best_index, (discrete_strip, placements, missing)) = passes.into_iter().next().expect("at least one ordering is kept");
+        self.discrete_strip = discrete_strip;
+        let solution = self.commit_strip_pass(placements, &missing);
+        log::info!(
+            "[SDR] coarse to fine over {:?} took {:?}, best is {:?}",
+            levels,
+            start_time.elapsed(),
+            ItemOrdering::ALL[best_index]
+        );
+        self.finish_solve(solution, start_time)
+    }
+
+    /// Runs a refining strip pass for every candidate in parallel, each on its own strip, and adds them to the report.
+    /// A candidate is the index of its ordering together with the copies and the missing copies of its pass at the
+    /// coarser resolution `coarser`. Returns the passes ranked as `ranked_growing_passes` does, with the index of the
+    /// ordering of their candidate. Once `terminator` stops, only the first candidate is refined.
+    fn ranked_refining_passes(
+        problem: &Problem,
+        instance: &SdrInstance,
+        resolution: fsize,
+        candidates: &[(usize, Vec<PlacedCopy>, MissingCopies)],
+        coarser: fsize,
+        terminator: &Terminator,
+        report: &mut SolveReport,
+    ) -> Vec<(usize, (DiscreteStrip, Placements, MissingCopies))> {
+        let passes = candidates
+            .par_iter()
+            .enumerate()
+            .filter(|(rank, _)| *rank == 0 || !terminator.should_stop())
+            .map(|(_, (index, copies, missing))| {
+                let (mut discrete_strip, placements, missing) = Self::refining_strip_pass(problem, instance, resolution, copies, missing, coarser);
+                discrete_strip.trim_after_last_occupied();
+                (*index, (discrete_strip, placements, missing))
+            })
+            .collect::<Vec<_>>();
+        for (_, (discrete_strip, _, _)) in &passes {
+            report.add_pass(&discrete_strip.counters);
+        }
+        passes
+            .into_iter()
+            .sorted_by_key(|(_, (discrete_strip, _, missing))| (!missing.is_empty(), discrete_strip.lines.len()))
+            .collect()
+    }
+
+    /// Places the copies of a pass at the coarser resolution `coarser` again in the same order, on a strip which grows
+    /// as far as they need. Every copy is searched for from two coarse lines before its coarse position, the finer
+    /// lines let it move closer to the copies before it. Its coarse orientation is tried first, it is kept unless
+    /// another one fits further left or lower. The copies missing from the coarser pass are tried at the end.
+    fn refining_strip_pass(
+        problem: &Problem,
+        instance: &SdrInstance,
+        resolution: fsize,
+        copies: &[PlacedCopy],
+        missing: &MissingCopies,
+        coarser: fsize,
+    ) -> (DiscreteStrip, Placements, MissingCopies) {
+        let mut discrete_strip = Self::growing_discrete_strip(problem, instance, resolution);
+        let frame = Self::discrete_frame(problem, instance, STRIP_LAYOUT_IDX, resolution);
+        let spacing = Self::spacing_offset(instance, resolution);
+        let mut placements: Placements = Vec::new();
+        let mut refined_missing: MissingCopies = Vec::new();
+        for copy in copies {
+            let item = instance.instance.item(copy.item_discrete.id);
+            let mut orientations = instance.orientations(item.id);
+            if let Some(coarse) = orientations.iter().position(|(item_discrete, _)| *item_discrete == copy.item_discrete) {
+                orientations.rotate_left(coarse);
+            }
+            let rotation_lines = orientations.iter().map(|(_, lines)| *lines).collect_vec();
+            let start_line = ((copy.x - 2.0 * coarser) / resolution).floor().max(0.0) as usize;
+            let start_time = Instant::now();
+            let placement = discrete_strip.find_placement(&rotation_lines, &frame.height(), start_line, 0.0, item.base_quality);
+            if let Some((first_line, offshoot, orientation)) = placement {
+                discrete_strip.add_segments(rotation_lines[orientation], offshoot, first_line);
+            }
+            discrete_strip.counters.add_item_time(item.id, start_time.elapsed());
+            match placement {
+                Some((first_line, offshoot, orientation)) => {
+                    let translation = (first_line as fsize * resolution, frame.height() - offshoot);
+                    placements.push(Self::frame_placement(STRIP_LAYOUT_IDX, item, &orientations[orientation].0, &frame, spacing, translation));
                 }
+                None => match refined_missing.last_mut() {
+                    Some((item_id, copies)) if *item_id == item.id => *copies += 1,
+                    _ => refined_missing.push((item.id, 1)),
+                },
+            }
+        }
+        for (item_id, copies) in missing {
+            let item = instance.instance.item(*item_id);
+            let orientations = instance.orientations(*item_id);
+            let amount = *copies as isize;
+            let item_placements = Self::discrete_placement(problem, instance, STRIP_LAYOUT_IDX, item, &resolution, &mut discrete_strip, &orientations, &amount);
+            if item_placements.len() < *copies {
+                refined_missing.push((*item_id, copies - item_placements.len()));
+            }
+            placements.extend(item_placements);
         }
-        println!("Total overlaps calls {}", get_overlaps_count());
-        println!("Runningtook {:?} ", start_time.elapsed());
+        (discrete_strip, placements, refined_missing)
+    }
 
-        match &mut self.problem {
-            Problem::BP(_) => {}
-            Problem::SP(sp_problem) => {
-                self.discrete_strip.trim_after_last_occupied();
-                sp_problem.modify_strip_in_back((self.discrete_strip.lines.len() - 1) as fsize * self.resolution);
-                log::info!(
-                    "[LBF] fitted strip width to {:.3}",
-                    sp_problem.strip_width()
-                )
+    /// Orientation and position of the copies placed by a strip pass at `resolution`
+    fn placed_copies(problem: &Problem, instance: &SdrInstance, resolution: fsize, placements: &Placements) -> Vec<PlacedCopy> {
+        let frame = Self::discrete_frame(problem, instance, STRIP_LAYOUT_IDX, resolution);
+        let (spacing_x, _) = Self::spacing_offset(instance, resolution);
+        placements
+            .iter()
+            .map(|(placement, mirrored)| {
+                let item = instance.instance.item(placement.item_id);
+                let item_discrete = ItemDiscrete::new(&item.id, &placement.d_transf.rotation(), *mirrored);
+                let (first_quadrant_x, _) = Self::first_quadrant_translation(item, &item_discrete);
+                let x = placement.d_transf.translation().0 - frame.x_min - first_quadrant_x - spacing_x;
+                PlacedCopy { item_discrete, x }
+            })
+            .collect()
+    }
+
+    fn solve_strip(&mut self, sorted_item_indices: Vec<usize>) -> Solution {
+        let order = PlacementOrder::new(sorted_item_indices);
+        let (discrete_strip, placements, missing) = Self::growing_strip_pass(&self.problem, &self.instance, self.resolution, &order);
+        self.discrete_strip = discrete_strip;
+        self.report.add_pass(&self.discrete_strip.counters);
+
+        self.discrete_strip.trim_after_last_occupied();
+        let solution = self.commit_strip_pass(placements, &missing);
+
+        if let Problem::SP(sp_problem) = &mut self.problem {
+            log::info!("[SDR] strip width {:.3}, usage {:.3} %", sp_problem.occupied_width(), solution.usage * 100.0);
+        }
+
+        solution
+    }
+
+    /// Solves a strip packing problem with `random_search` and then keeps shrinking the strip by `shrink_step`.
+    /// At every width the neighbours of the best order are tried until one fits all items,
+    /// the search stops when none does or when `time_limit` runs out.
+    /// Bin packing problems are only solved once.
+    pub fn minimize_strip(&mut self) -> Solution {
+        self.try_minimize_strip().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `minimize_strip`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_minimize_strip(&mut self) -> Result<Solution, SdrError> {
+        if let Problem::BP(_) = &self.problem {
+            return self.try_solve();
+        }
+        let start_time = self.start_report();
+        self.start_terminator();
+
+        let (mut best_order, mut best_solution) = self.sample_orders();
+
+        'shrink: loop {
+            let best_lines = self.discrete_strip.lines.len();
+            let best_width = (best_lines - 1) as fsize * self.resolution;
+            let target_width = best_width * (1.0 - self.config.shrink_step);
+            // the strip loses at least one line, otherwise the search would not progress
+            let num_lines = ((target_width / self.resolution).floor() as usize + 1).min(best_lines - 1);
+            if num_lines < 2 {
+                break;
+            }
+
+            let mut improved_order = None;
+            for order in best_order.neighbours() {
+                if self.terminator.should_stop() {
+                    break 'shrink;
+                }
+                let mut discrete_strip = Self::new_discrete_strip(&self.problem, &self.instance, num_lines, self.resolution);
+                let (placements, missing) = Self::strip_pass(&self.problem, &self.instance, &self.resolution, &mut discrete_strip, &order);
+                self.report.add_pass(&discrete_strip.counters);
+                if missing.is_empty() {
+                    discrete_strip.trim_after_last_occupied();
+                    self.discrete_strip = discrete_strip;
+                    best_solution = self.commit_strip_pass(placements, &missing);
+                    improved_order = Some(order);
+                    break;
+                }
             }
+            match improved_order {
+                Some(order) => best_order = order,
+                None => break,
+            }
+        }
+
+        log::info!(
+            "[SDR] minimized strip width to {:.3} in {:?}",
+            (self.discrete_strip.lines.len() - 1) as fsize * self.resolution,
+            start_time.elapsed()
+        );
+        self.finish_solve(best_solution, start_time)
+    }
+
+    /// Solves a strip packing problem by sampling `n_samples` placement orders with the PRNG of `prng_seed`,
+    /// starting from the order of `solve`. The first samples are uniformly random, the last `ls_frac` of them
+    /// are perturbations of the best order so far. Returns the narrowest strip in which all items fit.
+    /// Bin packing problems are only solved once.
+    pub fn random_search(&mut self) -> Solution {
+        self.try_random_search().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `random_search`, but fails instead of panicking when `Validation::Strict` finds collisions in the solution
+    pub fn try_random_search(&mut self) -> Result<Solution, SdrError> {
+        if let Problem::BP(_) = &self.problem {
+            return self.try_solve();
         }
+        let start_time = self.start_report();
+        self.start_terminator();
+        let (_, solution) = self.sample_orders();
+        self.finish_solve(solution, start_time)
+    }
+
+    /// Runs `random_search` on a strip packing problem, every improvement is committed to the problem.
+    /// Returns the best order together with its solution.
+    fn sample_orders(&mut self) -> (PlacementOrder, Solution) {
+        let mut rng = self.rng();
+        let n_orientations = (0..self.instance.instance.items().len())
+            .map(|item_id| self.instance.orientations(item_id).len())
+            .collect_vec();
+        let n_local_samples = (self.config.n_samples as f32 * self.config.ls_frac).round() as usize;
+        let n_uniform_samples = self.config.n_samples.saturating_sub(n_local_samples);
+        let mut num_lines = 0;
 
-        'outer: for item_index in sorted_item_indices {
-            while self.problem.missing_item_qtys()[item_index] > 0{
-                self.problem.place_item(opts.pop().unwrap());
-                #[allow(clippy::absurd_extreme_comparisons)]
-                if self.problem.placed_item_qtys().sum::<usize>() >= ITEM_LIMIT {
-                    break 'outer;
+        let mut best_order = PlacementOrder::new(self.item_order(self.config.item_ordering));
+        let mut best_solution = None;
+
+        for sample in 0..=self.config.n_samples {
+            if sample > 0 && self.terminator.should_stop() {
+                break;
+            }
+            let order = match sample {
+                0 => best_order.clone(),
+                s if s <= n_uniform_samples => PlacementOrder::random(&n_orientations, &mut rng),
+                _ => best_order.perturbed(&n_orientations, &mut rng),
+            };
+            let (mut discrete_strip, placements, missing) = match sample {
+                // the strip of the first sample grows as far as it needs, the others are as wide
+                0 => {
+                    let pass = Self::growing_strip_pass(&self.problem, &self.instance, self.resolution, &order);
+                    num_lines = pass.0.lines.len();
+                    pass
                 }
+                _ => {
+                    let mut discrete_strip = Self::new_discrete_strip(&self.problem, &self.instance, num_lines, self.resolution);
+                    let (placements, missing) = Self::strip_pass(&self.problem, &self.instance, &self.resolution, &mut discrete_strip, &order);
+                    (discrete_strip, placements, missing)
+                }
+            };
+            self.report.add_pass(&discrete_strip.counters);
+            discrete_strip.trim_after_last_occupied();
+            // the first sample is kept anyway, as `solve` would
+            let improves = best_solution.is_none() || (missing.is_empty() && discrete_strip.lines.len() < self.discrete_strip.lines.len());
+            if improves {
+                self.discrete_strip = discrete_strip;
+                best_solution = Some(self.commit_strip_pass(placements, &missing));
+                best_order = order;
             }
+        }
 
+        let solution = best_solution.expect("the first sample is always kept");
+        log::info!(
+            "[SDR] sampled {} orders, best strip width {:.3}",
+            self.config.n_samples,
+            (self.discrete_strip.lines.len() - 1) as fsize * self.resolution
+        );
+        (best_order, solution)
+    }
+
+    /// Places every copy of the items in the given order on `discrete_strip`, the problem is left untouched.
+    /// Returns the placements in order and the number of missing copies of the items which did not fully fit.
+    fn strip_pass(
+        problem: &Problem,
+        instance: &SdrInstance,
+        resolution: &fsize,
+        discrete_strip: &mut DiscreteStrip,
+        order: &PlacementOrder,
+    ) -> (Placements, MissingCopies) {
+        let mut placements: Placements = Vec::new();
+        let mut missing = Vec::new();
+        for item_index in &order.items {
+            let item = instance.instance.item(*item_index);
+            let amount = instance.instance.item_qty(*item_index) as isize;
+            let mut orientations = instance.orientations(*item_index);
+            if !orientations.is_empty() {
+                let first = order.first_orientations[*item_index] % orientations.len();
+                orientations.rotate_left(first);
+            }
+            let item_placements = Self::discrete_placement(problem, instance, STRIP_LAYOUT_IDX, item, resolution, discrete_strip, &orientations, &amount);
+            if item_placements.len() < amount as usize {
+                missing.push((*item_index, amount as usize - item_placements.len()));
+            }
+            placements.extend(item_placements);
         }
-        let solution= self.problem.create_solution(None);
-        
-        match &mut self.problem {
-            Problem::BP(_) => {}
-            Problem::SP(sp_problem) => {
-                println!("Strip Width {:?}, Usage {:?} % ", sp_problem.occupied_width(), sp_problem.layout.usage() * 100.00);
+        (placements, missing)
+    }
+
+    /// Strip pass on a strip which grows as far as the items need it
+    fn growing_strip_pass(problem: &Problem, instance: &SdrInstance, resolution: fsize, order: &PlacementOrder) -> (DiscreteStrip, Placements, MissingCopies) {
+        let mut discrete_strip = Self::growing_discrete_strip(problem, instance, resolution);
+        let (placements, missing) = Self::strip_pass(problem, instance, &resolution, &mut discrete_strip, order);
+        (discrete_strip, placements, missing)
+    }
+
+    /// Every orientation of the item is taller than the strip within its margins
+    fn too_tall(problem: &Problem, instance: &SdrInstance, item_id: usize) -> bool {
+        let strip_height = problem.get_layout(STRIP_LAYOUT_IDX).bin.bbox().height() - 2.0 * instance.edge_margin;
+        instance.orientation_bboxes(item_id).iter().all(|bbox| bbox.height() > strip_height)
+    }
+
+    /// Why the missing copies of a strip pass could not be placed
+    fn unplaced_items(&self, missing: &[(usize, usize)]) -> Vec<UnplacedItem> {
+        missing
+            .iter()
+            .map(|(item_id, copies)| {
+                let reason = match Self::too_tall(&self.problem, &self.instance, *item_id) {
+                    true => UnplacedReason::TooTall,
+                    false => {
+                        // an item which does not fit on an empty strip as wide as it can grow does not fit anywhere
+                        let empty_strip = Self::growing_discrete_strip(&self.problem, &self.instance, self.resolution);
+                        let height = Self::discrete_frame(&self.problem, &self.instance, STRIP_LAYOUT_IDX, self.resolution).height();
+                        let quality = self.instance.instance.item(*item_id).base_quality;
+                        match empty_strip.find_placement(&self.instance.rotation_lines(*item_id), &height, 0, 0.0, quality) {
+                            Some(_) => UnplacedReason::StripExhausted,
+                            None => UnplacedReason::NoRotationFits,
+                        }
+                    }
+                };
+                UnplacedItem { item_id: *item_id, copies: *copies, reason }
+            })
+            .collect_vec()
+    }
+
+    /// Fits the strip to `discrete_strip` and places the items of a pass on a fresh problem,
+    /// the missing copies are reported
+    fn commit_strip_pass(&mut self, placements: Placements, missing: &[(usize, usize)]) -> Solution {
+        let start_time = Instant::now();
+        let frame = Self::discrete_frame(&self.problem, &self.instance, STRIP_LAYOUT_IDX, self.resolution);
+        let frame_start = frame.x_min - self.problem.get_layout(STRIP_LAYOUT_IDX).bin.bbox().x_min;
+        let strip_width = frame_start + (self.discrete_strip.lines.len() - 1) as fsize * self.resolution;
+        if let Problem::SP(sp_problem) = &mut self.problem {
+            *sp_problem = SPProblem::new(sp_problem.instance.clone(), strip_width, self.config.cde_config);
+            log::info!(
+                "[LBF] fitted strip width to {:.3}",
+                sp_problem.strip_width()
+            )
+        }
+        self.mirrored_items.clear();
+
+        for (opt, mirrored) in placements {
+            Self::place_item(&mut self.problem, &mut self.mirrored_items, opt, mirrored);
+            #[allow(clippy::absurd_extreme_comparisons)]
+            if self.problem.placed_item_qtys().sum::<usize>() >= ITEM_LIMIT {
+                break;
             }
         }
-        
+        let solution = self.create_solution(None);
+        self.report.unplaced = self.unplaced_items(missing);
+        for unplaced in &self.report.unplaced {
+            log::warn!("[SDR] {} copies of item {} not placed: {:?}", unplaced.copies, unplaced.item_id, unplaced.reason);
+        }
+        self.report.commit_secs += start_time.elapsed().as_secs_f64();
+        self.notify_listener(&solution);
+        solution
+    }
+
+    /// Solution of the problem in its current state, with the usage of the material of the items
+    fn create_solution(&mut self, prev_solution: Option<&Solution>) -> Solution {
+        let mut solution = self.problem.create_solution(prev_solution);
+        self.instance.set_material_usage(&mut solution);
         solution
     }
 
+    /// Sets the deadline of the terminator to `time_limit` from now.
+    /// An invalid time limit, which `try_new` rejects, is ignored.
+    fn start_terminator(&mut self) {
+        let time_limit = self.config.time_limit.and_then(|time_limit| match Duration::try_from_secs_f32(time_limit) {
+            Ok(limit) => Some(limit),
+            Err(_) => {
+                log::warn!("[SDR] invalid time limit {}, the searches run their full budget", time_limit);
+                None
+            }
+        });
+        self.terminator.start(time_limit);
+    }
+
+    /// Token which cancels the searches of this optimizer, from any thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.terminator.cancellation.clone()
+    }
+
+    /// Sets a listener called with every improved solution as soon as it is found,
+    /// e.g. to send them over a channel. Searches still return their best solution.
+    pub fn set_solution_listener(&mut self, listener: impl FnMut(&Solution) + Send + 'static) {
+        self.solution_listener = Some(Box::new(listener));
+    }
+
+    /// Checks the layouts of the problem against the exact shapes of the items, with the collision detection engine
+    /// of jagua-rs. Returns the collisions which penetrate deeper than `validation_tolerance`.
+    pub fn validate(&self) -> Vec<Violation> {
+        self.problem
+            .layouts()
+            .iter()
+            .enumerate()
+            .flat_map(|(layout_index, layout)| {
+                validate_layout(layout, layout_index, &self.instance, &self.mirrored_items, self.resolution, self.config.validation_tolerance)
+            })
+            .collect()
+    }
+
+    /// Compacts and validates the solution and completes the report of a solve which started at `start_time`.
+    /// Returns the compacted solution.
+    fn finish_solve(&mut self, solution: Solution, start_time: Instant) -> Result<Solution, SdrError> {
+        let solution = match self.compact() {
+            true => {
+                let solution = self.create_solution(Some(&solution));
+                self.notify_listener(&solution);
+                solution
+            }
+            false => solution,
+        };
+        self.report.violations = match self.config.validation {
+            Validation::Off => vec![],
+            Validation::Report | Validation::Strict => self.validate(),
+        };
+        for violation in &self.report.violations {
+            log::warn!("[SDR] item {} in layout {} collides: {:?}", violation.item_id, violation.layout, violation);
+        }
+        self.report.finish(&self.problem, &solution, start_time);
+        match (self.config.validation, self.report.violations.is_empty()) {
+            (Validation::Strict, false) => Err(SdrError::InfeasibleSolution(self.report.violations.clone())),
+            _ => Ok(solution),
+        }
+    }
+
+    /// Slides the items of a strip packing problem left and down with `compaction_sweeps` sweeps of exact collision
+    /// checks, see `compaction::compact`, and fits the strip to them. Returns whether any item moved.
+    pub fn compact(&mut self) -> bool {
+        let Problem::SP(sp_problem) = &mut self.problem else {
+            return false;
+        };
+        if self.config.compaction_sweeps == 0 {
+            return false;
+        }
+        if self.instance.item_spacing > 0.0 || self.instance.edge_margin > 0.0 {
+            log::warn!("[SDR] compaction skipped, it does not keep the items apart by the spacing or off the margins");
+            return false;
+        }
+        let start_time = Instant::now();
+        let tolerance = self.config.validation_tolerance;
+        let mut layout = ExactLayout::new(&sp_problem.layout, &self.instance, &self.mirrored_items, self.resolution, tolerance);
+        // a quarter of the resolution keeps the items from jumping over thin parts of the others
+        let moved = compaction::compact(&mut layout, self.config.compaction_sweeps, self.resolution / 4.0, tolerance);
+        if moved <= tolerance {
+            self.report.compaction_secs += start_time.elapsed().as_secs_f64();
+            return false;
+        }
+
+        let bin_bbox = layout.bin_bbox();
+        let strip_width = (0..layout.items.len())
+            .map(|index| layout.item_bbox(index).x_max)
+            .fold(bin_bbox.x_min, fsize::max)
+            - bin_bbox.x_min;
+        log::info!("[SDR] compaction fitted strip width from {:.3} to {:.3}", sp_problem.strip_width(), strip_width);
+        *sp_problem = SPProblem::new(sp_problem.instance.clone(), strip_width, self.config.cde_config);
+        self.mirrored_items.clear();
+        for item in &layout.items {
+            let placement = PlacingOption { layout_idx: STRIP_LAYOUT_IDX, item_id: item.item_id, d_transf: item.d_transf };
+            Self::place_item(&mut self.problem, &mut self.mirrored_items, placement, item.mirrored);
+        }
+        self.report.compaction_secs += start_time.elapsed().as_secs_f64();
+        true
+    }
+
+    /// Starts a new report, returns the start of the solve
+    fn start_report(&mut self) -> Instant {
+        self.report = SolveReport::new(self.instance.discretization_time);
+        Instant::now()
+    }
+
+    fn notify_listener(&mut self, solution: &Solution) {
+        if let Some(listener) = &mut self.solution_listener {
+            listener(solution);
+        }
+    }
+
+    fn solve_bins(&mut self, sorted_item_indices: Vec<usize>) -> Solution {
+        'items: for item_index in &sorted_item_indices {
+            let item = &self.instance.instance.items()[*item_index].0;
+            let orientations = self.instance.orientations(*item_index);
+            let rotation_lines = self.instance.rotation_lines(*item_index);
+            if rotation_lines.is_empty() {
+                continue;
+            }
+
+            while self.problem.missing_item_qtys()[*item_index] > 0 {
+                let bin_index = match Self::select_bin(&self.problem, &self.instance, &self.discrete_bins, &rotation_lines, item.base_quality, self.config.bin_selection) {
+                    Some(bin_index) => bin_index,
+                    None => {
+                        // None of the opened bins can take the item, open the first bin type in which it fits
+                        let opened = self.problem.template_layout_indices_with_stock()
+                            .map(|layout_idx| DiscreteBin::new(layout_idx, &self.problem.get_layout(layout_idx).bin, self.resolution))
+                            .find(|d_bin| d_bin.discrete_strip.find_placement(&rotation_lines, &d_bin.bbox.height(), 0, 0.0, item.base_quality).is_some());
+                        match opened {
+                            Some(d_bin) => {
+                                self.discrete_bins.push(d_bin);
+                                self.discrete_bins.len() - 1
+                            }
+                            None => {
+                                let unplaced = UnplacedItem {
+                                    item_id: *item_index,
+                                    copies: self.problem.missing_item_qtys()[*item_index] as usize,
+                                    reason: self.unplaced_bin_reason(*item_index, &rotation_lines),
+                                };
+                                log::warn!("[SDR] {} copies of item {} not placed: {:?}", unplaced.copies, unplaced.item_id, unplaced.reason);
+                                self.report.unplaced.push(unplaced);
+                                continue 'items;
+                            }
+                        }
+                    }
+                };
+
+                let d_bin = &mut self.discrete_bins[bin_index];
+                let placements = Self::discrete_placement(&self.problem, &self.instance, d_bin.layout_idx, item, &self.resolution, &mut d_bin.discrete_strip, &orientations, &1);
+                for (placement, mirrored) in placements {
+                    let (layout_idx, _) = Self::place_item(&mut self.problem, &mut self.mirrored_items, placement, mirrored);
+                    d_bin.layout_idx = layout_idx;
+                }
+            }
+        }
+        // the bins are filled in a single pass
+        self.report.passes += 1;
+        for d_bin in &self.discrete_bins {
+            self.report.add_counters(&d_bin.discrete_strip.counters);
+        }
+
+        let solution = self.create_solution(None);
+        self.notify_listener(&solution);
+        log::info!("[SDR] bins used {}, usage {:.3} %", self.problem.layouts().len(), solution.usage * 100.0);
+
+        solution
+    }
+
+    /// Why an item fits in none of the opened bins nor in a bin in stock
+    fn unplaced_bin_reason(&self, item_id: usize, rotation_lines: &[&Vec<DiscreteLine>]) -> UnplacedReason {
+        let Instance::BP(bpi) = &self.instance.instance else {
+            unreachable!("only bin packing problems have bins")
+        };
+        let bboxes = self.instance.orientation_bboxes(item_id);
+        let too_tall = bpi.bins.iter().all(|(bin, _)| bboxes.iter().all(|bbox| bbox.height() > bin.bbox().height()));
+        if too_tall {
+            return UnplacedReason::TooTall;
+        }
+        let quality = self.instance.instance.item(item_id).base_quality;
+        // the bins which are out of stock are tried empty as well
+        let fits_empty_bin = bpi.bins.iter().enumerate().any(|(bin_id, (bin, _))| {
+            let d_bin = DiscreteBin::new(LayoutIndex::Template(bin_id), bin, self.resolution);
+            d_bin.discrete_strip.find_placement(rotation_lines, &d_bin.bbox.height(), 0, 0.0, quality).is_some()
+        });
+        match fits_empty_bin {
+            true => UnplacedReason::StripExhausted,
+            false => UnplacedReason::NoRotationFits,
+        }
+    }
+
+    /// Places an item in the problem and keeps track of it if it is mirrored
+    fn place_item(problem: &mut Problem, mirrored_items: &mut HashSet<(usize, PItemKey)>, placement: PlacingOption, mirrored: bool) -> (LayoutIndex, PItemKey) {
+        let (layout_idx, pik) = problem.place_item(placement);
+        if mirrored {
+            let layout_id = problem.get_layout(layout_idx).id();
+            mirrored_items.insert((layout_id, pik));
+        }
+        (layout_idx, pik)
+    }
+
+    /// Selects an opened bin in which one copy of the item fits, according to the `BinSelection` strategy.
+    fn select_bin(problem: &Problem, instance: &SdrInstance, discrete_bins: &[DiscreteBin], rotation_lines: &[&Vec<DiscreteLine>], quality: Option<usize>, bin_selection: BinSelection) -> Option<usize> {
+        let mut fitting_bins = discrete_bins.iter()
+            .enumerate()
+            .filter(|(_, d_bin)| d_bin.discrete_strip.find_placement(rotation_lines, &d_bin.bbox.height(), 0, 0.0, quality).is_some());
+
+        match bin_selection {
+            BinSelection::FirstFit => fitting_bins.next().map(|(i, _)| i),
+            BinSelection::BestFit => fitting_bins
+                .min_by_key(|(_, d_bin)| {
+                    let layout = problem.get_layout(d_bin.layout_idx);
+                    let material_area = instance.placed_material_area(layout.placed_items().values());
+                    NotNan::new(layout.bin.area - material_area).expect("free area is NaN")
+                })
+                .map(|(i, _)| i),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn discrete_placement(
         problem: &Problem,
+        instance: &SdrInstance,
+        layout_idx: LayoutIndex,
         item: &Item,
         resolution: &fsize,
         discrete_strip: &mut DiscreteStrip,
-        rotation_lines: &Vec<&Vec<DiscreteLine>>,
+        orientations: &[(ItemDiscrete, &Vec<DiscreteLine>)],
         amount: &isize,
-        item_index: &usize,
-    ) -> Vec<PlacingOption>{
-        let mut placements: Vec<PlacingOption> = Vec::new();
-        match problem {
-            Problem::BP(_) => {}
-            Problem::SP(sp_problem) => {
-                let results = discrete_strip.try_fit_segments(
-                    rotation_lines, 
-                    &sp_problem.strip_height(), 
-                    resolution,
-                    amount,
-                    item_index
-                );
-                for result in results{
-                    let translation_1 = result.0.decompose().translation; 
-
-                    let new_transformation = match &item.allowed_rotation {
-                        AllowedRotation::Discrete(angles) => {
-                            let angle = angles[result.1];
-                            let translation_2 = item.move_to_first_quadrant(angle).decompose().translation; 
-                            let added_translation = (
-                                translation_1.0.into_inner() + translation_2.0.into_inner(), 
-                                translation_1.1.into_inner() + translation_2.1.into_inner(), 
-                            );
-                            DTransformation::new(
-                                angle,            // Use the selected angle
-                                added_translation,              // Use the added translation
-                            )
-                        },
-                        _ => {
-                            
-                            let translation_2 = item.move_to_first_quadrant(0.0).decompose().translation; 
-                            let added_translation = (
-                                translation_1.0.into_inner() + translation_2.0.into_inner(), 
-                                translation_1.1.into_inner() + translation_2.1.into_inner(), 
-                            );
-                            DTransformation::new(
-                                0.0,            // Default rotation
-                                added_translation,              // Use the added translation
-                            )
-                        },
-                    };
-                    // println!("pushing {}, {:?}", item.id, new_transformation);
-                    placements.push(PlacingOption { layout_idx: STRIP_LAYOUT_IDX, item_id: item.id, d_transf: new_transformation });
-                }
-            }
+    ) -> Vec<(PlacingOption, bool)>{
+        let mut placements: Vec<(PlacingOption, bool)> = Vec::new();
+        let rotation_lines = orientations.iter().map(|(_, lines)| *lines).collect_vec();
+        let bbox = Self::discrete_frame(problem, instance, layout_idx, *resolution);
+        let (spacing_x, spacing_y) = Self::spacing_offset(instance, *resolution);
+        let start_time = Instant::now();
+        let results = discrete_strip.try_fit_segments(
+            &rotation_lines,
+            &bbox.height(),
+            resolution,
+            amount,
+            &item.id,
+            item.base_quality,
+        );
+        discrete_strip.counters.add_item_time(item.id, start_time.elapsed());
+        for (transformation, orientation) in results {
+            let (x, y) = transformation.decompose().translation;
+            let (item_discrete, _) = orientations[orientation];
+            placements.push(Self::frame_placement(layout_idx, item, &item_discrete, &bbox, (spacing_x, spacing_y), (x.into_inner(), y.into_inner())));
         }
         placements
     }
 
+    /// Placement of an item in the orientation of `item_discrete`, translated by `translation` within the discrete
+    /// frame `frame` of its layout as the strip places it
+    fn frame_placement(
+        layout_idx: LayoutIndex,
+        item: &Item,
+        item_discrete: &ItemDiscrete,
+        frame: &AARectangle,
+        (spacing_x, spacing_y): (fsize, fsize),
+        translation: (fsize, fsize),
+    ) -> (PlacingOption, bool) {
+        let angle = item_discrete.rotation.into_inner();
+        let (first_quadrant_x, first_quadrant_y) = Self::first_quadrant_translation(item, item_discrete);
+        let d_transf = DTransformation::new(
+            angle,
+            (frame.x_min + translation.0 + first_quadrant_x + spacing_x, frame.y_min + translation.1 + first_quadrant_y - spacing_y),
+        );
+        (PlacingOption { layout_idx, item_id: item.id, d_transf }, item_discrete.mirrored)
+    }
+
+    /// Translation which moves the item, rotated and mirrored as in `item_discrete`, to the first quadrant
+    fn first_quadrant_translation(item: &Item, item_discrete: &ItemDiscrete) -> (fsize, fsize) {
+        let angle = item_discrete.rotation.into_inner();
+        let first_quadrant = match item_discrete.mirrored {
+            true => mirror_polygon(&item.shape).move_to_first_quadrant(angle),
+            false => item.move_to_first_quadrant(angle),
+        };
+        let (x, y) = first_quadrant.decompose().translation;
+        (x.into_inner(), y.into_inner())
+    }
 }
@@ -1,19 +1,29 @@
 use std::collections::HashMap;
-use std::time::Instant;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use itertools::Itertools;
+use jagua_rs::entities::bin::Bin;
+use jagua_rs::entities::instances::bin_packing::BPInstance;
 use jagua_rs::entities::instances::instance::Instance;
+use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
 use jagua_rs::entities::instances::strip_packing::SPInstance;
 use jagua_rs::entities::item::Item;
-use jagua_rs::fsize;
+use jagua_rs::entities::placed_item::PlacedItem;
+use jagua_rs::entities::solution::Solution;
+use jagua_rs::{fsize, PI};
+use jagua_rs::geometry::convex_hull::convex_hull_from_points;
 use jagua_rs::geometry::d_transformation::DTransformation;
 use jagua_rs::geometry::geo_enums::AllowedRotation;
 use jagua_rs::geometry::geo_traits::{Shape, Transformable};
 use jagua_rs::geometry::primitives::aa_rectangle::AARectangle;
 use jagua_rs::geometry::primitives::point::Point;
 use jagua_rs::geometry::primitives::simple_polygon::SimplePolygon;
+use jagua_rs::entities::quality_zone::{InferiorQualityZone, N_QUALITIES};
 use jagua_rs::geometry::transformation::Transformation;
-use jagua_rs::io::json_instance::{JsonInstance, JsonItem, JsonShape, JsonSimplePoly};
+use jagua_rs::io::json_instance::{JsonBin, JsonInstance, JsonItem, JsonPoly, JsonQualityZone, JsonShape, JsonSimplePoly};
+use jagua_rs::io::parser::pretransform_bin;
 use jagua_rs::util::config::CDEConfig;
 use jagua_rs::util::polygon_simplification;
 use jagua_rs::util::polygon_simplification::{PolySimplConfig, PolySimplMode};
@@ -21,68 +31,304 @@ use ordered_float::OrderedFloat;
 use rayon::iter::IndexedParallelIterator;
 use rayon::iter::ParallelIterator;
 use rayon::prelude::*;
-use dashmap::DashMap;
 
-use crate::discrete_item::Discretizable;
+use crate::discrete_item::{congruent, dilate_lines, mirror_polygon, Discretizable, ItemComponent, ItemGeometry};
 use crate::discrete_line::DiscreteLine;
+use crate::discretization_cache::DiscretizationCache;
+use crate::sdr_config::{RotationSampling, SDRConfig};
+use crate::sdr_error::SdrError;
+use crate::resolution_advisor::{try_advise_resolution, ResolutionAdvice, ResolutionTarget};
+use crate::io::json_instance_ext::JsonInstanceExt;
 
 #[derive(Debug)]
 pub struct SdrParse {
     poly_simpl_config: PolySimplConfig,
     cde_config: CDEConfig,
     center_polygons: bool,
+    rotation_sampling: RotationSampling,
+    item_spacing: fsize,
+    edge_margin: fsize,
+    discretization_cache: Option<DiscretizationCache>,
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct ItemDiscrete{
     pub id: usize,
     pub rotation: OrderedFloat<fsize>,
+    /// The item is mirrored in the y axis before it is rotated
+    pub mirrored: bool,
 }
 
 impl ItemDiscrete{
-    pub fn new(id: &usize, rotation: &fsize) -> ItemDiscrete{
-        ItemDiscrete { id: *id, rotation: OrderedFloat(*rotation) }
+    pub fn new(id: &usize, rotation: &fsize, mirrored: bool) -> ItemDiscrete{
+        ItemDiscrete { id: *id, rotation: OrderedFloat(*rotation), mirrored }
     }
 }
 
 #[derive(Debug,Clone)]
 pub struct SdrInstance{
     pub instance: Instance,
+    /// Discretized lines of every orientation which is not in `aliases`
     pub items: HashMap<ItemDiscrete, Vec<DiscreteLine>>,
+    /// Orientations congruent to one of the orientations listed before them, over all items, mapped to the first
+    /// of those. They share its lines and are not discretized, those congruent to another orientation of the
+    /// same item are not tried at all, see `orientations`.
+    pub aliases: HashMap<ItemDiscrete, ItemDiscrete>,
+    /// Angles tried for every item, sampled for items which can rotate freely
+    pub rotations: HashMap<usize, Vec<fsize>>,
+    /// Geometry of the items which is not captured by their jagua shape, e.g. holes
+    pub geometry: HashMap<usize, ItemGeometry>,
+    /// Mirror images of the shapes of the items which may be mirrored
+    pub mirrored_shapes: HashMap<usize, SimplePolygon>,
+    /// Defects in the material of the strip, in the coordinates of the strip
+    pub strip_defects: Vec<ItemComponent>,
+    /// Inferior quality zones of the strip, in the coordinates of the strip
+    pub strip_quality_zones: Vec<InferiorQualityZone>,
+    /// Time it took to discretize all orientations of the items
+    pub discretization_time: Duration,
+    /// Minimum gap between two items, the lines of the items are dilated by half of it
+    pub item_spacing: fsize,
+    /// Minimum gap between the items and the bottom, the top and the start of the strip
+    pub edge_margin: fsize,
+    /// Where the discretized orientations are cached on disk, also when the instance is discretized again
+    pub discretization_cache: Option<DiscretizationCache>,
 }
 
 impl SdrInstance{
-    fn new<'a>(instance: Instance, it: Vec<(Item,usize)>, resolution: fsize) -> SdrInstance {
-        let items: DashMap<ItemDiscrete, Vec<DiscreteLine>> = DashMap::new();
+    /// Instance whose items are not discretized yet, see `discretize`.
+    /// The rotations, the spacing, the margin and the cache are those of `parser`.
+    fn new(instance: Instance, it: Vec<(Item,usize)>, geometry: HashMap<usize, ItemGeometry>, mirrorable: &[usize], parser: &SdrParse) -> SdrInstance {
+        let rotations: HashMap<usize, Vec<fsize>> = it
+            .iter()
+            .map(|(item, _)| {
+                let angles = match &item.allowed_rotation {
+                    AllowedRotation::None => vec![0.0],
+                    AllowedRotation::Discrete(angles) => angles.clone(),
+                    AllowedRotation::Continuous => sample_rotations(&item.shape, parser.rotation_sampling),
+                };
+                (item.id, angles)
+            })
+            .collect();
 
-        let start_time = Instant::now();
-        // Iterate through each item in the instance
-        it.par_iter().for_each(|item| {
-            // let item_time = Instant::now();
-            // Create an ItemDiscrete for this item
-            match &item.0.allowed_rotation {
-                AllowedRotation::Discrete(angles) => {
-                    for rotation in angles {
-                        let item_discrete = ItemDiscrete::new(&item.0.id, rotation);
-                        let discrete_lines = item.0.discretize_shape(resolution, *rotation);
-                        items.insert(item_discrete, discrete_lines);
+        let mirrored_shapes: HashMap<usize, SimplePolygon> = it
+            .iter()
+            .filter(|(item, _)| mirrorable.contains(&item.id))
+            .map(|(item, _)| (item.id, mirror_polygon(&item.shape)))
+            .collect();
+
+        SdrInstance {
+            instance,
+            items: HashMap::new(),
+            aliases: HashMap::new(),
+            rotations,
+            geometry,
+            mirrored_shapes,
+            strip_defects: Vec::new(),
+            strip_quality_zones: Vec::new(),
+            discretization_time: Duration::ZERO,
+            item_spacing: parser.item_spacing,
+            edge_margin: parser.edge_margin,
+            discretization_cache: parser.discretization_cache.clone(),
+        }
+    }
+
+    fn discretize(&mut self, resolution: fsize) {
+        self.aliases = self.orientation_aliases();
+        (self.items, self.discretization_time) = self.discretize_items(resolution);
+    }
+
+    /// Same instance with the items discretized at another resolution, the orientations stay the same
+    pub fn rediscretize(&self, resolution: fsize) -> SdrInstance {
+        let (items, discretization_time) = self.discretize_items(resolution);
+        SdrInstance { items, discretization_time, ..self.clone() }
+    }
+
+    /// Every orientation tried for an item together with its discretized lines, the mirrored ones follow
+    /// the others. An orientation congruent to one before it is left out, it would only be tried twice.
+    pub fn orientations(&self, item_id: usize) -> Vec<(ItemDiscrete, &Vec<DiscreteLine>)> {
+        let mut discretized = Vec::new();
+        self.orientation_keys(item_id)
+            .into_iter()
+            .filter_map(|item_discrete| {
+                let original = self.aliases.get(&item_discrete).unwrap_or(&item_discrete);
+                if discretized.contains(original) {
+                    return None;
+                }
+                discretized.push(*original);
+                self.items.get(original).map(|lines| (item_discrete, lines))
+            })
+            .collect()
+    }
+
+    /// Discretized lines of an orientation of an item, also when it is an alias
+    pub fn lines(&self, item_discrete: &ItemDiscrete) -> Option<&Vec<DiscreteLine>> {
+        self.items.get(self.aliases.get(item_discrete).unwrap_or(item_discrete))
+    }
+
+    /// Bounding box of the shape of an item in each of its `orientations`
+    pub fn orientation_bboxes(&self, item_id: usize) -> Vec<AARectangle> {
+        self.orientations(item_id)
+            .into_iter()
+            .map(|(item_discrete, _)| {
+                let shape = self.oriented_shape(&item_discrete);
+                shape.transform_clone(&Transformation::from_rotation(item_discrete.rotation.into_inner())).bbox()
+            })
+            .collect_vec()
+    }
+
+    /// All orientations of an item, congruent or not, in the order of `orientations`
+    fn orientation_keys(&self, item_id: usize) -> Vec<ItemDiscrete> {
+        let mirrored = match self.mirrored_shapes.contains_key(&item_id) {
+            true => vec![false, true],
+            false => vec![false],
+        };
+        mirrored
+            .into_iter()
+            .cartesian_product(self.rotations[&item_id].iter())
+            .map(|(mirrored, rotation)| ItemDiscrete::new(&item_id, rotation, mirrored))
+            .collect()
+    }
+
+    /// Shape of an item, mirrored if the orientation is, but not rotated yet
+    fn oriented_shape(&self, item_discrete: &ItemDiscrete) -> &SimplePolygon {
+        match item_discrete.mirrored {
+            true => &self.mirrored_shapes[&item_discrete.id],
+            false => self.instance.item(item_discrete.id).shape.as_ref(),
+        }
+    }
+
+    /// Maps every orientation which is congruent to an orientation listed before it to the first of them,
+    /// so identical shapes and symmetric rotations are only discretized once
+    fn orientation_aliases(&self) -> HashMap<ItemDiscrete, ItemDiscrete> {
+        let no_geometry = ItemGeometry::default();
+        // the rotated shapes of the orientations which are not aliased, by their number of points
+        let mut originals: HashMap<usize, Vec<(ItemDiscrete, SimplePolygon, ItemGeometry)>> = HashMap::new();
+        let mut aliases = HashMap::new();
+        for (item, _) in self.instance.items() {
+            let geometry = self.geometry.get(&item.id).unwrap_or(&no_geometry);
+            let mirrored_geometry = geometry.mirror_clone();
+            for item_discrete in self.orientation_keys(item.id) {
+                let rotation = Transformation::from_rotation(item_discrete.rotation.into_inner());
+                let shape = self.oriented_shape(&item_discrete).transform_clone(&rotation);
+                let geometry = match item_discrete.mirrored {
+                    true => mirrored_geometry.transform_clone(&rotation),
+                    false => geometry.transform_clone(&rotation),
+                };
+                let candidates = originals.entry(shape.points.len()).or_default();
+                match candidates.iter().find(|(_, original, original_geometry)| congruent(original, original_geometry, &shape, &geometry)) {
+                    Some((original, _, _)) => {
+                        aliases.insert(item_discrete, *original);
                     }
-                },
-                _ => {
-                    let item_discrete = ItemDiscrete::new(&item.0.id, &0.0);   
-                    let discrete_lines = item.0.discretize_shape(resolution, 0.0); 
-                    items.insert(item_discrete, discrete_lines);
-                },
+                    None => candidates.push((item_discrete, shape, geometry)),
+                }
             }
+        }
+        aliases
+    }
 
-        });
-        println!("Total discretization processing time: {:?}", start_time.elapsed());
+    /// Discretizes every orientation of the items which is not an alias, the mirrored ones included, and dilates
+    /// them by half the item spacing. The orientations found in the cache are loaded instead, the others are
+    /// stored in it. Returns the lines of every orientation and the time it took.
+    fn discretize_items(&self, resolution: fsize) -> (HashMap<ItemDiscrete, Vec<DiscreteLine>>, Duration) {
+        let half_spacing = self.item_spacing / 2.0;
+        let cache = self.discretization_cache.as_ref();
+        let no_geometry = ItemGeometry::default();
+        let cache_hits = AtomicUsize::new(0);
+        let discretize = |shape: &SimplePolygon, geometry: &ItemGeometry, rotation: fsize| {
+            let key = cache.map(|cache| (cache, cache.key(shape, geometry, rotation, half_spacing, resolution)));
+            if let Some(lines) = key.and_then(|(cache, key)| cache.load(key)) {
+                cache_hits.fetch_add(1, Ordering::Relaxed);
+                return lines;
+            }
+            let lines = shape.discretize_geometry(resolution, rotation, geometry);
+            let lines = match half_spacing > 0.0 {
+                true => dilate_lines(&lines, half_spacing, resolution),
+                false => lines,
+            };
+            if let Some((cache, key)) = key {
+                cache.store(key, &lines);
+            }
+            lines
+        };
 
-        let items: HashMap<_, _> = items.into_iter().collect();
-        
-        SdrInstance {
-            instance: instance.clone(), 
-            items,
+        let start_time = Instant::now();
+        let mirrored_geometries: HashMap<usize, ItemGeometry> = self
+            .mirrored_shapes
+            .keys()
+            .map(|item_id| (*item_id, self.geometry.get(item_id).unwrap_or(&no_geometry).mirror_clone()))
+            .collect();
+        let items: HashMap<ItemDiscrete, Vec<DiscreteLine>> = self
+            .instance
+            .items()
+            .iter()
+            .flat_map(|(item, _)| self.orientation_keys(item.id))
+            .filter(|item_discrete| !self.aliases.contains_key(item_discrete))
+            .collect_vec()
+            .into_par_iter()
+            .map(|item_discrete| {
+                let geometry = match item_discrete.mirrored {
+                    true => &mirrored_geometries[&item_discrete.id],
+                    false => self.geometry.get(&item_discrete.id).unwrap_or(&no_geometry),
+                };
+                let lines = discretize(self.oriented_shape(&item_discrete), geometry, item_discrete.rotation.into_inner());
+                (item_discrete, lines)
+            })
+            .collect();
+        let discretization_time = start_time.elapsed();
+        log::debug!(
+            "[SDR] discretized {} orientations in {:?}, {} congruent ones share their lines",
+            items.len(),
+            discretization_time,
+            self.aliases.len()
+        );
+        if let Some(cache) = cache {
+            log::info!(
+                "[SDR] loaded {} of {} orientations from the cache in {}",
+                cache_hits.into_inner(),
+                items.len(),
+                cache.dir().display()
+            );
+        }
+
+        (items, discretization_time)
+    }
+
+    /// Discretized lines of every rotation tried for an item, in the order of `orientations`
+    pub fn rotation_lines(&self, item_id: usize) -> Vec<&Vec<DiscreteLine>> {
+        self.orientations(item_id).into_iter().map(|(_, lines)| lines).collect()
+    }
+
+    /// Area of the material of an item, its holes excluded. The jagua shape of an item is only its outer ring,
+    /// or the convex hull of its components, so its area is not the material of the item.
+    pub fn material_area(&self, item_id: usize) -> fsize {
+        let shape = &self.instance.item(item_id).shape;
+        match self.geometry.get(&item_id) {
+            Some(ItemGeometry { components, .. }) if !components.is_empty() => components
+                .iter()
+                .map(|component| component.outer.area() - component.holes.iter().map(|hole| hole.area()).sum::<fsize>())
+                .sum(),
+            Some(ItemGeometry { holes, .. }) => shape.area() - holes.iter().map(|hole| hole.area()).sum::<fsize>(),
+            None => shape.area(),
+        }
+    }
+
+    /// Area of the material of the placed items, see `material_area`
+    pub fn placed_material_area<'a>(&self, placed_items: impl IntoIterator<Item = &'a PlacedItem>) -> fsize {
+        placed_items.into_iter().map(|placed_item| self.material_area(placed_item.item_id)).sum()
+    }
+
+    /// Sets the usage of a solution and of its layouts to the material of the placed items over the area of the bins,
+    /// jagua-rs counts the area of their shapes instead
+    pub fn set_material_usage(&self, solution: &mut Solution) {
+        let (mut material_area, mut bin_area) = (0.0, 0.0);
+        for s_layout in &mut solution.layout_snapshots {
+            let layout_material_area = self.placed_material_area(s_layout.placed_items.values());
+            s_layout.usage = layout_material_area / s_layout.bin.area;
+            material_area += layout_material_area;
+            bin_area += s_layout.bin.area;
+        }
+        if bin_area > 0.0 {
+            solution.usage = material_area / bin_area;
         }
     }
 }
@@ -97,10 +343,90 @@ impl SdrParse{
             poly_simpl_config,
             cde_config,
             center_polygons,
+            rotation_sampling: RotationSampling::default(),
+            item_spacing: 0.0,
+            edge_margin: 0.0,
+            discretization_cache: None,
+        }
+    }
+
+    pub fn from_config(config: &SDRConfig, center_polygons: bool) -> SdrParse {
+        let poly_simpl_config = match config.poly_simpl_tolerance {
+            Some(tolerance) => PolySimplConfig::Enabled { tolerance },
+            None => PolySimplConfig::Disabled,
+        };
+        SdrParse {
+            poly_simpl_config,
+            cde_config: config.cde_config,
+            center_polygons,
+            rotation_sampling: config.rotation_sampling,
+            item_spacing: config.item_spacing,
+            edge_margin: config.edge_margin,
+            discretization_cache: None,
         }
     }
 
+    /// Caches the discretized orientations of the items in `cache_dir`, see `DiscretizationCache`
+    pub fn with_discretization_cache(self, cache_dir: &Path) -> SdrParse {
+        let simplification_tolerance = match self.poly_simpl_config {
+            PolySimplConfig::Enabled { tolerance } => Some(tolerance),
+            PolySimplConfig::Disabled => None,
+        };
+        SdrParse { discretization_cache: Some(DiscretizationCache::new(cache_dir, simplification_tolerance)), ..self }
+    }
+
     pub fn parse(&self, json_instance: &JsonInstance, resolution: fsize) -> SdrInstance {
+        self.parse_with_ext(json_instance, &JsonInstanceExt::default(), resolution)
+    }
+
+    pub fn try_parse(&self, json_instance: &JsonInstance, resolution: fsize) -> Result<SdrInstance, SdrError> {
+        self.try_parse_with_ext(json_instance, &JsonInstanceExt::default(), resolution)
+    }
+
+    /// Parses an instance together with the fields which are specific to SDR
+    pub fn parse_with_ext(&self, json_instance: &JsonInstance, json_ext: &JsonInstanceExt, resolution: fsize) -> SdrInstance {
+        self.try_parse_with_ext(json_instance, json_ext, resolution).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Parses an instance together with the fields which are specific to SDR.
+    /// The instance is validated first, jagua-rs panics on degenerate shapes.
+    pub fn try_parse_with_ext(&self, json_instance: &JsonInstance, json_ext: &JsonInstanceExt, resolution: fsize) -> Result<SdrInstance, SdrError> {
+        if !(resolution.is_finite() && resolution > 0.0) {
+            return Err(SdrError::InvalidResolution(resolution));
+        }
+        let mut sdr_instance = self.try_parse_shapes(json_instance, json_ext)?;
+        sdr_instance.discretize(resolution);
+        Ok(sdr_instance)
+    }
+
+    /// Parses an instance together with the fields which are specific to SDR, at the resolution advised for it
+    /// by `advise_resolution`. `config` is only used to time the probes of a `ResolutionTarget::TimeBudget`.
+    pub fn parse_advised(&self, json_instance: &JsonInstance, json_ext: &JsonInstanceExt, config: &SDRConfig, target: ResolutionTarget) -> (SdrInstance, ResolutionAdvice) {
+        self.try_parse_advised(json_instance, json_ext, config, target).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_advised(&self, json_instance: &JsonInstance, json_ext: &JsonInstanceExt, config: &SDRConfig, target: ResolutionTarget) -> Result<(SdrInstance, ResolutionAdvice), SdrError> {
+        let mut sdr_instance = self.try_parse_shapes(json_instance, json_ext)?;
+        let advice = try_advise_resolution(&sdr_instance, config, target)?;
+        sdr_instance.discretize(advice.resolution);
+        Ok((sdr_instance, advice))
+    }
+
+    /// Parses everything but the discrete lines of the items
+    fn try_parse_shapes(&self, json_instance: &JsonInstance, json_ext: &JsonInstanceExt) -> Result<SdrInstance, SdrError> {
+        validate_instance(json_instance, json_ext)?;
+        if !(self.item_spacing.is_finite() && self.item_spacing >= 0.0) {
+            return Err(SdrError::InvalidInstance(format!("item spacing {} is not positive", self.item_spacing)));
+        }
+        if !(self.edge_margin.is_finite() && self.edge_margin >= 0.0) {
+            return Err(SdrError::InvalidStrip(format!("edge margin {} is not positive", self.edge_margin)));
+        }
+        if let Some(json_strip) = &json_instance.strip {
+            if 2.0 * self.edge_margin >= json_strip.height {
+                return Err(SdrError::InvalidStrip(format!("edge margin {} leaves no room on the strip", self.edge_margin)));
+            }
+        }
+
         let items: Vec<(Item, usize)> = json_instance
             .items
             .par_iter()
@@ -108,20 +434,49 @@ impl SdrParse{
             .map(|(item_id, json_item)| self.parse_item(json_item, item_id))
             .collect();
 
+        let geometry: HashMap<usize, ItemGeometry> = json_instance
+            .items
+            .iter()
+            .zip(items.iter())
+            .filter_map(|(json_item, (item, _))| {
+                self.parse_item_geometry(json_item, item).map(|item_geometry| (item.id, item_geometry))
+            })
+            .collect();
+
         let instance: Instance = match (json_instance.bins.as_ref(), json_instance.strip.as_ref()) {
-            (Some(_), None) => {
-                panic!("BP problem")
+            (Some(json_bins), None) => {
+                let bins = json_bins
+                    .par_iter()
+                    .enumerate()
+                    .map(|(bin_id, json_bin)| self.parse_bin(json_bin, bin_id))
+                    .collect::<Result<Vec<(Bin, usize)>, SdrError>>()?;
+                BPInstance::new(items.clone(), bins).into()
             }
             (None, Some(json_strip)) => SPInstance::new(items.clone(), json_strip.height).into(),
-            (Some(_), Some(_)) => {
-                panic!("Both bins and strip packing specified, has to be one or the other")
+            (Some(_), Some(_)) | (None, None) => {
+                return Err(SdrError::InvalidInstance("the instance has to specify either bins or a strip".to_string()))
             }
-            (None, None) => panic!("Neither bins or strips specified"),
         };
 
-        let sdr_ins = SdrInstance::new(instance, items, resolution);
-        
-        sdr_ins
+        let mirrorable = json_ext
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, json_item_ext)| json_item_ext.allow_mirroring)
+            .map(|(item_id, _)| item_id)
+            .collect_vec();
+
+        let mut sdr_instance = SdrInstance::new(instance, items, geometry, &mirrorable, self);
+        if let Some(json_strip_ext) = &json_ext.strip {
+            sdr_instance.strip_defects = json_strip_ext
+                .defects
+                .iter()
+                .flat_map(|defect| self.parse_defect(defect))
+                .collect_vec();
+            sdr_instance.strip_quality_zones =
+                self.parse_quality_zones(&json_strip_ext.zones).map_err(SdrError::InvalidStrip)?;
+        }
+        Ok(sdr_instance)
     }
 
     pub fn parse_item(&self, json_item: &JsonItem, item_id: usize) -> (Item, usize) {
@@ -132,11 +487,17 @@ impl SdrParse{
             JsonShape::SimplePolygon(sp) => {
                 convert_json_simple_poly(sp, self.poly_simpl_config, PolySimplMode::Inflate)
             }
-            JsonShape::Polygon(_) => {
-                unimplemented!("No support for polygon shapes yet")
+            JsonShape::Polygon(jp) => {
+                // the holes are kept in the geometry of the SdrInstance
+                convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Inflate)
             }
-            JsonShape::MultiPolygon(_) => {
-                unimplemented!("No support for multipolygon shapes yet")
+            JsonShape::MultiPolygon(jmp) => {
+                // the item is represented by the convex hull of its components, which are kept in the geometry of the SdrInstance
+                let points = jmp
+                    .iter()
+                    .flat_map(|jp| convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Inflate).points)
+                    .collect_vec();
+                SimplePolygon::new(convex_hull_from_points(points))
             }
         };
 
@@ -174,9 +535,249 @@ impl SdrParse{
 
         (item, json_item.demand as usize)
     }
+
+    /// Parses the geometry of an item which its jagua `Item` cannot hold, in the pretransformed frame of `item`.
+    /// Returns `None` for items fully described by their shape.
+    pub fn parse_item_geometry(&self, json_item: &JsonItem, item: &Item) -> Option<ItemGeometry> {
+        let geometry = match &json_item.shape {
+            JsonShape::Polygon(jp) if !jp.inner.is_empty() => ItemGeometry {
+                holes: self.parse_holes(jp),
+                components: vec![],
+            },
+            JsonShape::MultiPolygon(jmp) => ItemGeometry {
+                holes: vec![],
+                components: jmp
+                    .iter()
+                    .map(|jp| ItemComponent {
+                        outer: convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Inflate),
+                        holes: self.parse_holes(jp),
+                    })
+                    .collect_vec(),
+            },
+            _ => return None,
+        };
+        Some(geometry.transform_clone(&item.pretransform))
+    }
+
+    /// Groups the quality zones per quality, the zones are inflated.
+    /// Fails with the reason on zones with holes, which are not supported.
+    pub fn parse_quality_zones(&self, zones: &[JsonQualityZone]) -> Result<Vec<InferiorQualityZone>, String> {
+        (0..N_QUALITIES)
+            .map(|quality| {
+                let zones = zones
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, zone)| zone.quality == quality)
+                    .map(|(i, zone)| match &zone.shape {
+                        JsonShape::Rectangle { width, height } => {
+                            Ok(SimplePolygon::from(AARectangle::new(0.0, 0.0, *width, *height)))
+                        }
+                        JsonShape::SimplePolygon(jsp) => {
+                            Ok(convert_json_simple_poly(jsp, self.poly_simpl_config, PolySimplMode::Inflate))
+                        }
+                        JsonShape::Polygon(_) | JsonShape::MultiPolygon(_) => {
+                            Err(format!("quality zone {}: quality zones with holes are not supported", i))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(InferiorQualityZone::new(quality, zones))
+            })
+            .collect()
+    }
+
+    /// Defects are inflated, holes in a defect are ignored
+    pub fn parse_defect(&self, json_shape: &JsonShape) -> Vec<ItemComponent> {
+        let outers = match json_shape {
+            JsonShape::Rectangle { width, height } => {
+                vec![SimplePolygon::from(AARectangle::new(0.0, 0.0, *width, *height))]
+            }
+            JsonShape::SimplePolygon(jsp) => {
+                vec![convert_json_simple_poly(jsp, self.poly_simpl_config, PolySimplMode::Inflate)]
+            }
+            JsonShape::Polygon(jp) => {
+                vec![convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Inflate)]
+            }
+            JsonShape::MultiPolygon(jmp) => jmp
+                .iter()
+                .map(|jp| convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Inflate))
+                .collect_vec(),
+        };
+        outers
+            .into_iter()
+            .map(|outer| ItemComponent { outer, holes: vec![] })
+            .collect_vec()
+    }
+
+    /// Holes are deflated, the discretized item never claims free space
+    fn parse_holes(&self, jp: &JsonPoly) -> Vec<SimplePolygon> {
+        jp.inner
+            .iter()
+            .map(|jsp| convert_json_simple_poly(jsp, self.poly_simpl_config, PolySimplMode::Deflate))
+            .collect_vec()
+    }
+
+    /// Fails on multipolygon bins and on quality zones with holes, which are not supported
+    pub fn parse_bin(&self, json_bin: &JsonBin, bin_id: usize) -> Result<(Bin, usize), SdrError> {
+        let multipolygon = || SdrError::InvalidBin {
+            bin_id,
+            reason: "multipolygon bins are not supported".to_string(),
+        };
+        let bin_outer = match &json_bin.shape {
+            JsonShape::Rectangle { width, height } => {
+                SimplePolygon::from(AARectangle::new(0.0, 0.0, *width, *height))
+            }
+            JsonShape::SimplePolygon(jsp) => {
+                convert_json_simple_poly(jsp, self.poly_simpl_config, PolySimplMode::Deflate)
+            }
+            JsonShape::Polygon(jp) => {
+                convert_json_simple_poly(&jp.outer, self.poly_simpl_config, PolySimplMode::Deflate)
+            }
+            JsonShape::MultiPolygon(_) => return Err(multipolygon()),
+        };
+
+        let bin_holes = match &json_bin.shape {
+            JsonShape::SimplePolygon(_) | JsonShape::Rectangle { .. } => vec![],
+            JsonShape::Polygon(jp) => jp
+                .inner
+                .iter()
+                .map(|jsp| convert_json_simple_poly(jsp, self.poly_simpl_config, PolySimplMode::Inflate))
+                .collect_vec(),
+            JsonShape::MultiPolygon(_) => return Err(multipolygon()),
+        };
+
+        let material_value =
+            (bin_outer.area() - bin_holes.iter().map(|hole| hole.area()).sum::<fsize>()) as u64;
+
+        let quality_zones = self
+            .parse_quality_zones(&json_bin.zones)
+            .map_err(|reason| SdrError::InvalidBin { bin_id, reason })?;
+
+        let base_bin = Bin::new(
+            bin_id,
+            bin_outer,
+            material_value,
+            Transformation::empty(),
+            bin_holes,
+            quality_zones,
+            self.cde_config,
+        );
+
+        let bin = match self.center_polygons {
+            false => base_bin,
+            true => {
+                let centering_transform = centering_transformation(&base_bin.outer);
+                pretransform_bin(&base_bin, &centering_transform.compose())
+            }
+        };
+
+        let stock = json_bin.stock.unwrap_or(u64::MAX) as usize;
+
+        Ok((bin, stock))
+    }
     
 }
 
+/// Checks everything in an instance which would make the parsing panic
+fn validate_instance(json_instance: &JsonInstance, json_ext: &JsonInstanceExt) -> Result<(), SdrError> {
+    match (json_instance.bins.as_ref(), json_instance.strip.as_ref()) {
+        (Some(json_bins), None) => {
+            for (bin_id, json_bin) in json_bins.iter().enumerate() {
+                let invalid_bin = |reason| SdrError::InvalidBin { bin_id, reason };
+                if let JsonShape::MultiPolygon(_) = json_bin.shape {
+                    return Err(invalid_bin("multipolygon bins are not supported".to_string()));
+                }
+                validate_shape(&json_bin.shape).map_err(invalid_bin)?;
+                validate_quality_zones(&json_bin.zones).map_err(invalid_bin)?;
+            }
+        }
+        (None, Some(json_strip)) => {
+            if !(json_strip.height.is_finite() && json_strip.height > 0.0) {
+                return Err(SdrError::InvalidStrip(format!("height {} is not strictly positive", json_strip.height)));
+            }
+        }
+        (Some(_), Some(_)) => {
+            return Err(SdrError::InvalidInstance("both bins and a strip are specified, it has to be one or the other".to_string()))
+        }
+        (None, None) => return Err(SdrError::InvalidInstance("neither bins nor a strip are specified".to_string())),
+    }
+
+    for (item_id, json_item) in json_instance.items.iter().enumerate() {
+        let invalid_item = |reason| SdrError::InvalidItem { item_id, reason };
+        validate_shape(&json_item.shape).map_err(invalid_item)?;
+        if let Some(angle) = json_item.allowed_orientations.iter().flatten().find(|angle| !angle.is_finite()) {
+            return Err(invalid_item(format!("orientation {} is not finite", angle)));
+        }
+    }
+
+    if let Some(json_strip_ext) = &json_ext.strip {
+        for (i, defect) in json_strip_ext.defects.iter().enumerate() {
+            validate_shape(defect).map_err(|reason| SdrError::InvalidStrip(format!("defect {}: {}", i, reason)))?;
+        }
+        validate_quality_zones(&json_strip_ext.zones).map_err(SdrError::InvalidStrip)?;
+    }
+    Ok(())
+}
+
+fn validate_quality_zones(zones: &[JsonQualityZone]) -> Result<(), String> {
+    for (i, zone) in zones.iter().enumerate() {
+        match &zone.shape {
+            JsonShape::Polygon(_) | JsonShape::MultiPolygon(_) => {
+                return Err(format!("quality zone {}: quality zones with holes are not supported", i))
+            }
+            shape => validate_shape(shape).map_err(|reason| format!("quality zone {}: {}", i, reason))?,
+        }
+    }
+    Ok(())
+}
+
+fn validate_shape(json_shape: &JsonShape) -> Result<(), String> {
+    match json_shape {
+        JsonShape::Rectangle { width, height } => match width.is_finite() && height.is_finite() && *width > 0.0 && *height > 0.0 {
+            true => Ok(()),
+            false => Err(format!("rectangle of {} by {} has no area", width, height)),
+        },
+        JsonShape::SimplePolygon(jsp) => validate_simple_poly(jsp),
+        JsonShape::Polygon(jp) => validate_poly(jp),
+        JsonShape::MultiPolygon(jmp) => {
+            if jmp.is_empty() {
+                return Err("multipolygon without polygons".to_string());
+            }
+            jmp.iter()
+                .enumerate()
+                .try_for_each(|(i, jp)| validate_poly(jp).map_err(|reason| format!("polygon {}: {}", i, reason)))
+        }
+    }
+}
+
+fn validate_poly(jp: &JsonPoly) -> Result<(), String> {
+    validate_simple_poly(&jp.outer)?;
+    jp.inner
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, jsp)| validate_simple_poly(jsp).map_err(|reason| format!("hole {}: {}", i, reason)))
+}
+
+/// The checks of `SimplePolygon::new`, which panics on a degenerate polygon
+fn validate_simple_poly(jsp: &JsonSimplePoly) -> Result<(), String> {
+    if jsp.0.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+        return Err("polygon with a coordinate which is not finite".to_string());
+    }
+    if jsp.0.is_empty() {
+        return Err("polygon without points".to_string());
+    }
+    let points = json_simple_poly_to_points(jsp);
+    if points.len() < 3 {
+        return Err(format!("polygon with {} points, at least 3 are needed", points.len()));
+    }
+    if points.iter().map(|p| (OrderedFloat(p.0), OrderedFloat(p.1))).unique().count() != points.len() {
+        return Err("polygon with duplicate points".to_string());
+    }
+    if SimplePolygon::calculate_area(&points) == 0.0 {
+        return Err("polygon without area".to_string());
+    }
+    Ok(())
+}
+
 fn convert_json_simple_poly(
     s_json_shape: &JsonSimplePoly,
     simpl_config: PolySimplConfig,
@@ -184,14 +785,12 @@ fn convert_json_simple_poly(
 ) -> SimplePolygon {
     let shape = SimplePolygon::new(json_simple_poly_to_points(s_json_shape));
 
-    let shape = match simpl_config {
+    match simpl_config {
         PolySimplConfig::Enabled { tolerance } => {
             polygon_simplification::simplify_shape(&shape, simpl_mode, tolerance)
         }
         PolySimplConfig::Disabled => shape,
-    };
-
-    shape
+    }
 }
 
 fn json_simple_poly_to_points(jsp: &JsonSimplePoly) -> Vec<Point> {
@@ -227,6 +826,44 @@ pub fn pretransform_item(item: &Item, extra_pretransf: &Transformation) -> Item
     )
 }
 
+/// Angles to try for an item which can rotate freely, in [0, 2π)
+pub fn sample_rotations(shape: &SimplePolygon, rotation_sampling: RotationSampling) -> Vec<fsize> {
+    let angles = match rotation_sampling {
+        RotationSampling::Uniform(n) => (0..n.max(1)).map(|i| i as fsize * 2.0 * PI / n.max(1) as fsize).collect_vec(),
+        RotationSampling::PrincipalAxes => {
+            // second moments of area around the centroid
+            let Point(cx, cy) = shape.centroid();
+            let (mut ixx, mut iyy, mut ixy) = (0.0, 0.0, 0.0);
+            for edge in shape.edge_iter() {
+                let (x0, y0) = (edge.start.0 - cx, edge.start.1 - cy);
+                let (x1, y1) = (edge.end.0 - cx, edge.end.1 - cy);
+                let cross = x0 * y1 - x1 * y0;
+                ixx += cross * (y0 * y0 + y0 * y1 + y1 * y1);
+                iyy += cross * (x0 * x0 + x0 * x1 + x1 * x1);
+                ixy += cross * (x0 * y1 + 2.0 * x0 * y0 + 2.0 * x1 * y1 + x1 * y0);
+            }
+            // ixx and iyy are 12 times the integrals of y² and x², ixy is 24 times the integral of xy
+            let axis = 0.5 * ixy.atan2(iyy - ixx);
+            (0..4).map(|i| i as fsize * PI / 2.0 - axis).collect_vec()
+        }
+        RotationSampling::LongestHullEdge => {
+            let hull = SimplePolygon::new(convex_hull_from_points(shape.points.clone()));
+            let longest = hull
+                .edge_iter()
+                .max_by_key(|edge| OrderedFloat((edge.end.0 - edge.start.0).hypot(edge.end.1 - edge.start.1)))
+                .expect("convex hull without edges");
+            let edge_angle = (longest.end.1 - longest.start.1).atan2(longest.end.0 - longest.start.0);
+            vec![-edge_angle, PI - edge_angle]
+        }
+    };
+    angles
+        .into_iter()
+        .map(|angle| angle.rem_euclid(2.0 * PI))
+        .sorted_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .dedup_by(|a, b| (a - b).abs() < 1e-4)
+        .collect_vec()
+}
+
 pub fn centering_transformation(shape: &SimplePolygon) -> DTransformation {
     let Point(cx, cy) = shape.centroid();
     DTransformation::new(0.0, (-cx, -cy))
@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use jagua_rs::entities::problems::problem::Problem;
+use jagua_rs::entities::solution::Solution;
+use jagua_rs::fsize;
+use serde::{Deserialize, Serialize};
+
+use crate::discrete_line::StripCounters;
+use crate::validation::Violation;
+
+/// Statistics of the last solve of an `SDROptimizer`, summed over all strip passes it made.
+/// Times are in seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct SolveReport {
+    /// Discretization of the items, done once when the instance is parsed
+    pub discretization_secs: f64,
+    /// Search for the placements of the items on the discrete strips, summed over the passes.
+    /// Passes run in parallel, so this is cumulative CPU time and can exceed `total_secs`
+    pub placement_secs: f64,
+    /// Placement of the kept passes in the jagua-rs problem
+    pub commit_secs: f64,
+    /// Compaction of the final solution
+    pub compaction_secs: f64,
+    /// Whole solve, discretization excluded
+    pub total_secs: f64,
+    /// Strip passes made, one per order tried
+    pub passes: usize,
+    /// Overlap checks between two intervals
+    pub overlap_checks: usize,
+    /// Lines on which the placement of an item was tried
+    pub lines_scanned: usize,
+    /// Width of the final strip, undefined for bin packing problems
+    pub strip_width: Option<fsize>,
+    pub usage: fsize,
+    /// Time spent placing every item, per item id, summed over the passes like `placement_secs`
+    pub item_placement_secs: Vec<f64>,
+    /// Copies missing from the final solution
+    pub unplaced: Vec<UnplacedItem>,
+    /// Collisions of the final solution found by the validation
+    pub violations: Vec<Violation>,
+}
+
+/// Copies of an item which could not be placed, the rest of the layout is produced without them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct UnplacedItem {
+    pub item_id: usize,
+    pub copies: usize,
+    pub reason: UnplacedReason,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnplacedReason {
+    /// Every orientation of the item is taller than the strip, or than every bin
+    TooTall,
+    /// No orientation of the item fits anywhere on an empty strip or in an empty bin,
+    /// e.g. because of defects, quality zones or the shape of the bins
+    NoRotationFits,
+    /// The item fits on an empty strip, but not next to the items placed before it.
+    /// For bin packing, no bin in which it fits is left in stock.
+    StripExhausted,
+}
+
+impl SolveReport {
+    pub fn new(discretization_time: Duration) -> Self {
+        Self { discretization_secs: discretization_time.as_secs_f64(), ..Self::default() }
+    }
+
+    /// Adds the work done during a strip pass
+    pub fn add_pass(&mut self, counters: &StripCounters) {
+        self.passes += 1;
+        self.add_counters(counters);
+    }
+
+    /// Adds the work done on a strip
+    pub fn add_counters(&mut self, counters: &StripCounters) {
+        self.overlap_checks += counters.overlap_checks.get();
+        self.lines_scanned += counters.lines_scanned.get();
+        for (item_id, time) in counters.item_times.iter().enumerate() {
+            self.add_item_secs(item_id, time.as_secs_f64());
+        }
+    }
+
+    /// Adds the passes of another report, e.g. one filled in parallel
+    pub fn merge(&mut self, other: &SolveReport) {
+        self.passes += other.passes;
+        self.overlap_checks += other.overlap_checks;
+        self.lines_scanned += other.lines_scanned;
+        for (item_id, secs) in other.item_placement_secs.iter().enumerate() {
+            self.add_item_secs(item_id, *secs);
+        }
+    }
+
+    /// Records the final solution of a solve which started at `start_time`
+    pub fn finish(&mut self, problem: &Problem, solution: &Solution, start_time: Instant) {
+        self.total_secs = start_time.elapsed().as_secs_f64();
+        self.strip_width = match problem {
+            Problem::SP(sp_problem) => Some(sp_problem.strip_width()),
+            Problem::BP(_) => None,
+        };
+        self.usage = solution.usage;
+    }
+
+    fn add_item_secs(&mut self, item_id: usize, secs: f64) {
+        if self.item_placement_secs.len() <= item_id {
+            self.item_placement_secs.resize(item_id + 1, 0.0);
+        }
+        self.item_placement_secs[item_id] += secs;
+        self.placement_secs += secs;
+    }
+}
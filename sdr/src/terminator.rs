@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cancels a running search from another thread, all clones of a token share their state
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Tells a search to stop once its deadline has passed or when it is cancelled
+#[derive(Debug, Clone, Default)]
+pub struct Terminator {
+    pub deadline: Option<Instant>,
+    pub cancellation: CancellationToken,
+}
+
+impl Terminator {
+    /// Sets the deadline to `time_limit` from now, or removes it
+    pub fn start(&mut self, time_limit: Option<Duration>) {
+        self.deadline = time_limit.map(|limit| Instant::now() + limit);
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.cancellation.is_cancelled() || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use jagua_rs::collision_detection::cd_engine::CDEngine;
+use jagua_rs::collision_detection::hazard::{Hazard, HazardEntity};
+use jagua_rs::entities::instances::instance::Instance;
+use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
+use jagua_rs::entities::layout::Layout;
+use jagua_rs::entities::placed_item::PItemKey;
+use jagua_rs::fsize;
+use jagua_rs::geometry::d_transformation::DTransformation;
+use jagua_rs::geometry::geo_enums::GeoPosition;
+use jagua_rs::geometry::geo_traits::{SeparationDistance, Shape, Transformable};
+use jagua_rs::geometry::primitives::aa_rectangle::AARectangle;
+use jagua_rs::geometry::primitives::point::Point;
+use jagua_rs::geometry::primitives::simple_polygon::SimplePolygon;
+use jagua_rs::util::config::SPSurrogateConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::discrete_item::{ItemComponent, ItemGeometry};
+use crate::sdr_parse::SdrInstance;
+
+/// A placed item which collides with a hazard in its exact geometry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Violation {
+    /// Index of the layout in the problem
+    pub layout: usize,
+    pub item_id: usize,
+    pub kind: ViolationKind,
+    /// How far the item and the hazard penetrate each other, estimated on points sampled along both borders
+    pub penetration_depth: fsize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The item overlaps another placed item
+    Overlap { other_item_id: usize },
+    /// The item lies partly outside of the bin or the strip
+    OutOfBin,
+    /// The item lies partly in a hole of the bin
+    InBinHole { hole_id: usize },
+    /// The item lies partly on a defect of the strip
+    OnDefect { defect_id: usize },
+    /// The item lies partly in a zone of a lower quality than it requires
+    InQualityZone { quality: usize },
+}
+
+/// A layout rebuilt in a collision detection engine of jagua-rs with the true geometry of its items,
+/// together with the strip defects and quality zones of the instance.
+/// The engine only knows the outer shapes of the items and holds mirrored items unmirrored, so the collisions it
+/// detects are measured again on the true geometry: an item nested in the hole of another one does not collide.
+/// As on the discrete strip, an item without a base quality may lie on any quality zone.
+pub struct ExactLayout {
+    cde: CDEngine,
+    bin: Arc<SimplePolygon>,
+    kinds: HashMap<HazardEntity, ViolationKind>,
+    materials: HashMap<HazardEntity, Vec<ItemComponent>>,
+    pub items: Vec<ExactItem>,
+    /// Largest distance between the points sampled along the borders to measure a penetration
+    sample_step: fsize,
+    /// Penetration depth up to which a collision is ignored
+    tolerance: fsize,
+}
+
+/// A placed item of an `ExactLayout`
+pub struct ExactItem {
+    pub item_id: usize,
+    pub d_transf: DTransformation,
+    pub mirrored: bool,
+    base_quality: Option<usize>,
+    /// Shape of the item before it is transformed, with its surrogate
+    shape: SimplePolygon,
+    /// Polygons making up the item before it is transformed
+    material: Vec<ItemComponent>,
+}
+
+impl ExactItem {
+    fn entity(&self) -> HazardEntity {
+        HazardEntity::PlacedItem { id: self.item_id, dt: self.d_transf }
+    }
+
+    fn placed_shape(&self, d_transf: &DTransformation) -> SimplePolygon {
+        self.shape.transform_clone(&d_transf.compose())
+    }
+
+    fn placed_material(&self, d_transf: &DTransformation) -> Vec<ItemComponent> {
+        let transformation = d_transf.compose();
+        self.material.iter().map(|component| component.transform_clone(&transformation)).collect()
+    }
+}
+
+impl ExactLayout {
+    /// `mirrored_items` holds the layout id and key of the placed items which are mirrored
+    pub fn new(layout: &Layout, instance: &SdrInstance, mirrored_items: &HashSet<(usize, PItemKey)>, sample_step: fsize, tolerance: fsize) -> Self {
+        let mut cde = layout.bin.base_cde.as_ref().clone();
+        let surrogate_config = cde.config().item_surrogate_config;
+        let mut kinds = HashMap::new();
+        let mut materials = HashMap::new();
+
+        // the strip defects and quality zones only exist for SDR, the bin of the strip does not know them
+        if let Instance::SP(_) = &instance.instance {
+            for (defect_id, defect) in instance.strip_defects.iter().enumerate() {
+                let entity = HazardEntity::BinHole { id: layout.bin.holes.len() + defect_id };
+                kinds.insert(entity, ViolationKind::OnDefect { defect_id });
+                materials.insert(entity, vec![defect.clone()]);
+                cde.register_hazard(Hazard::new(entity, Arc::new(with_surrogate(defect.outer.clone(), surrogate_config))));
+            }
+            for quality_zone in &instance.strip_quality_zones {
+                for (id, zone) in quality_zone.zones.iter().enumerate() {
+                    let entity = HazardEntity::InferiorQualityZone { quality: quality_zone.quality, id };
+                    cde.register_hazard(Hazard::new(entity, Arc::new(with_surrogate(zone.as_ref().clone(), surrogate_config))));
+                }
+            }
+        }
+
+        let items = layout
+            .placed_items
+            .iter()
+            .sorted_by_key(|(pik, _)| *pik)
+            .map(|(pik, pi)| {
+                let item = instance.instance.item(pi.item_id);
+                let mirrored = mirrored_items.contains(&(layout.id(), pik));
+                let (shape, geometry) = match mirrored {
+                    true => (
+                        with_surrogate(instance.mirrored_shapes[&pi.item_id].clone(), surrogate_config),
+                        instance.geometry.get(&pi.item_id).map(ItemGeometry::mirror_clone),
+                    ),
+                    false => (item.shape.as_ref().clone(), instance.geometry.get(&pi.item_id).cloned()),
+                };
+                let material = material(shape.clone(), geometry);
+                ExactItem { item_id: pi.item_id, d_transf: pi.d_transf, mirrored, base_quality: item.base_quality, shape, material }
+            })
+            .collect_vec();
+        for item in &items {
+            cde.register_hazard(Hazard::new(item.entity(), Arc::new(item.placed_shape(&item.d_transf))));
+            materials.insert(item.entity(), item.placed_material(&item.d_transf));
+        }
+        for hazard in cde.all_hazards() {
+            materials
+                .entry(hazard.entity)
+                .or_insert_with(|| vec![ItemComponent { outer: hazard.shape.as_ref().clone(), holes: vec![] }]);
+        }
+
+        Self { cde, bin: layout.bin.outer.clone(), kinds, materials, items, sample_step, tolerance }
+    }
+
+    pub fn bin_bbox(&self) -> AARectangle {
+        self.bin.bbox()
+    }
+
+    /// Bounding box of the true shape of an item where it is placed
+    pub fn item_bbox(&self, index: usize) -> AARectangle {
+        let item = &self.items[index];
+        item.placed_shape(&item.d_transf).bbox()
+    }
+
+    /// Hazards the item at `index` would penetrate deeper than the tolerance if it were placed with `d_transf`,
+    /// with the depth of the penetration. The item itself is ignored where it is placed now.
+    pub fn collisions(&self, index: usize, d_transf: &DTransformation) -> Vec<(HazardEntity, fsize)> {
+        let item = &self.items[index];
+        let own_entity = item.entity();
+        let irrelevant_hazards = self
+            .cde
+            .all_hazards()
+            .map(|hazard| hazard.entity)
+            .filter(|entity| match entity {
+                HazardEntity::InferiorQualityZone { quality, .. } => item.base_quality.is_none_or(|base_quality| *quality >= base_quality),
+                _ => *entity == own_entity,
+            })
+            .collect_vec();
+        let shape = item.placed_shape(d_transf);
+        let mut detected = vec![];
+        self.cde.collect_poly_collisions(&shape, &irrelevant_hazards, &mut detected);
+        if detected.is_empty() {
+            return vec![];
+        }
+
+        let material = item.placed_material(d_transf);
+        detected
+            .into_iter()
+            .map(|entity| {
+                let depth = match entity.position() {
+                    GeoPosition::Interior => penetration_depth(&material, &self.materials[&entity], self.sample_step),
+                    GeoPosition::Exterior => protrusion_depth(&material, &self.bin, self.sample_step),
+                };
+                (entity, depth)
+            })
+            .filter(|(_, depth)| *depth > self.tolerance)
+            .collect()
+    }
+
+    /// Whether the item at `index` would collide with any hazard if it were placed with `d_transf`
+    pub fn collides(&self, index: usize, d_transf: &DTransformation) -> bool {
+        !self.collisions(index, d_transf).is_empty()
+    }
+
+    /// Places the item at `index` with `d_transf` instead
+    pub fn move_item(&mut self, index: usize, d_transf: DTransformation) {
+        let item = &self.items[index];
+        self.cde.deregister_hazard(item.entity(), true);
+        self.materials.remove(&item.entity());
+
+        let item = &mut self.items[index];
+        item.d_transf = d_transf;
+        self.cde.register_hazard(Hazard::new(item.entity(), Arc::new(item.placed_shape(&d_transf))));
+        self.materials.insert(item.entity(), item.placed_material(&d_transf));
+    }
+
+    /// Every collision of the layout, an overlap is reported once
+    pub fn violations(&self, layout_index: usize) -> Vec<Violation> {
+        let indices: HashMap<HazardEntity, usize> = self.items.iter().enumerate().map(|(index, item)| (item.entity(), index)).collect();
+        let mut violations = vec![];
+        for (index, item) in self.items.iter().enumerate() {
+            for (entity, penetration_depth) in self.collisions(index, &item.d_transf) {
+                let kind = match entity {
+                    HazardEntity::PlacedItem { id, .. } => {
+                        // an overlap is reported by the first of both items
+                        if indices[&entity] < index {
+                            continue;
+                        }
+                        ViolationKind::Overlap { other_item_id: id }
+                    }
+                    HazardEntity::BinExterior => ViolationKind::OutOfBin,
+                    HazardEntity::BinHole { id } => match self.kinds.get(&entity) {
+                        Some(kind) => *kind,
+                        None => ViolationKind::InBinHole { hole_id: id },
+                    },
+                    HazardEntity::InferiorQualityZone { quality, .. } => ViolationKind::InQualityZone { quality },
+                };
+                violations.push(Violation { layout: layout_index, item_id: item.item_id, kind, penetration_depth });
+            }
+        }
+        violations
+    }
+}
+
+/// Checks every placed item of a layout against the bin, the strip defects and quality zones of the instance
+/// and the other items, see `ExactLayout`.
+/// Only violations which penetrate deeper than `tolerance` are returned, an overlap is reported once.
+pub fn validate_layout(
+    layout: &Layout,
+    layout_index: usize,
+    instance: &SdrInstance,
+    mirrored_items: &HashSet<(usize, PItemKey)>,
+    sample_step: fsize,
+    tolerance: fsize,
+) -> Vec<Violation> {
+    ExactLayout::new(layout, instance, mirrored_items, sample_step, tolerance).violations(layout_index)
+}
+
+/// Polygons making up an item, with their holes
+fn material(shape: SimplePolygon, geometry: Option<ItemGeometry>) -> Vec<ItemComponent> {
+    match geometry {
+        Some(geometry) if !geometry.components.is_empty() => geometry.components,
+        Some(geometry) => vec![ItemComponent { outer: shape, holes: geometry.holes }],
+        None => vec![ItemComponent { outer: shape, holes: vec![] }],
+    }
+}
+
+/// The collision detection engine needs the surrogate of every shape it checks
+fn with_surrogate(mut shape: SimplePolygon, config: SPSurrogateConfig) -> SimplePolygon {
+    shape.generate_surrogate(config);
+    shape
+}
+
+/// Largest distance over which a point of the border of one material lies inside the other one
+fn penetration_depth(a: &[ItemComponent], b: &[ItemComponent], sample_step: fsize) -> fsize {
+    let a_in_b = sample_borders(a, sample_step).map(|point| depth_in(b, &point)).fold(0.0, fsize::max);
+    let b_in_a = sample_borders(b, sample_step).map(|point| depth_in(a, &point)).fold(0.0, fsize::max);
+    a_in_b.max(b_in_a)
+}
+
+/// Largest distance over which a point of the border of the material lies outside of the bin
+fn protrusion_depth(material: &[ItemComponent], bin: &SimplePolygon, sample_step: fsize) -> fsize {
+    sample_borders(material, sample_step)
+        .filter_map(|point| match bin.separation_distance(&point) {
+            (GeoPosition::Exterior, distance) => Some(distance),
+            (GeoPosition::Interior, _) => None,
+        })
+        .fold(0.0, fsize::max)
+}
+
+/// Distance from a point inside the material to its border, 0 outside of it
+fn depth_in(material: &[ItemComponent], point: &Point) -> fsize {
+    material
+        .iter()
+        .filter_map(|component| {
+            let (GeoPosition::Interior, outer_distance) = component.outer.separation_distance(point) else {
+                return None;
+            };
+            component.holes.iter().try_fold(outer_distance, |depth, hole| match hole.separation_distance(point) {
+                (GeoPosition::Exterior, distance) => Some(depth.min(distance)),
+                (GeoPosition::Interior, _) => None,
+            })
+        })
+        .fold(0.0, fsize::max)
+}
+
+/// Points along the borders of the outer polygons and holes of a material, at most `sample_step` apart
+fn sample_borders(material: &[ItemComponent], sample_step: fsize) -> impl Iterator<Item = Point> + '_ {
+    material
+        .iter()
+        .flat_map(|component| iter::once(&component.outer).chain(&component.holes))
+        .flat_map(move |polygon| polygon.edge_iter())
+        .flat_map(move |edge| {
+            let n_samples = ((edge.diameter() / sample_step).ceil() as usize).max(1);
+            (0..n_samples).map(move |i| {
+                let t = i as fsize / n_samples as fsize;
+                Point(edge.start.0 + t * (edge.end.0 - edge.start.0), edge.start.1 + t * (edge.end.1 - edge.start.1))
+            })
+        })
+}
@@ -2,18 +2,60 @@
 mod test_placement {
     use jagua_rs::fsize;
     use sdr::io::svg_util::{SvgDrawOptions, SvgLayoutTheme};
-    use sdr::sdr_parse::SdrParse;
-    use sdr::{io::layout_to_svg::s_layout_to_svg, sdr_config::SDRConfig};
-    use sdr::sdr_optimizer::SDROptimizer;
+    use sdr::sdr_parse::{ItemDiscrete, SdrInstance, SdrParse};
+    use sdr::{io::layout_to_svg::{s_layout_to_svg, s_sdr_layout_to_svg, s_sdr_layout_to_svg_with_strip}, sdr_config::{BrkgaConfig, ItemOrdering, RotationSampling, SDRConfig, Validation}};
+    use sdr::brkga::Chromosome;
+    use sdr::sdr_optimizer::{PlacementOrder, SDROptimizer};
+    use sdr::discrete_line::{DiscreteLine, DiscreteStrip, Interval};
+    use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
+    use sdr::solve_report::{UnplacedItem, UnplacedReason};
+    use sdr::sdr_error::SdrError;
+    use jagua_rs::io::json_instance::{JsonShape, JsonSimplePoly};
+    use sdr::validation::ViolationKind;
+    use sdr::resolution_advisor::ResolutionTarget;
+    use sdr::discrete_item::{Discretizable, ItemGeometry};
+    use jagua_rs::geometry::geo_traits::{SeparationDistance, Shape, Transformable};
+    use jagua_rs::entities::placing_option::PlacingOption;
+    use jagua_rs::geometry::d_transformation::DTransformation;
+    use jagua_rs::entities::problems::problem_generic::{ProblemGeneric, STRIP_LAYOUT_IDX};
     use test_case::test_case;
     use sdr::io::{self};
+    use sdr::io::json_output::JsonSdrSolution;
+    use sdr::EPOCH;
+    use std::cell::Cell;
     use std::fs::File;
     use std::io::Write;
     use std::path::Path as OtherPath;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
     use jagua_rs::util::polygon_simplification::PolySimplConfig;
+    use jagua_rs::entities::solution::Solution;
+    use svg::Document;
     use simplelog::{Config, LevelFilter, WriteLogger};
     use chrono::{DateTime, Local};
 
+    /// Parses the instance at `instance_path` with its extension, if any, at `resolution`
+    fn parse_instance(instance_path: &str, config: &SDRConfig, resolution: fsize) -> SdrInstance {
+        let instance = OtherPath::new(instance_path);
+        let json_instance = io::read_json_instance(instance);
+        let json_ext = io::read_json_instance_ext(instance);
+        SdrParse::from_config(config, true).parse_with_ext(&json_instance, &json_ext, resolution)
+    }
+
+    /// Solves the instance in a single pass, every item has to be placed
+    fn solve_complete(instance: SdrInstance, config: SDRConfig, resolution: fsize) -> (SDROptimizer, Solution) {
+        let mut optimizer = SDROptimizer::new(instance, config, resolution);
+        let sol = optimizer.solve();
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        (optimizer, sol)
+    }
+
+    /// Writes the SVG to the temporary directory, for a look at the layout
+    fn write_test_svg(svg: &Document, name: &str) {
+        io::write_svg(svg, &std::env::temp_dir().join(name));
+    }
+
     #[test_case("../assets/shirt2.json", 1.0; "shirt2")]
     #[test_case("../assets/swim.json", 36.0; "swim")]
     #[test_case("../assets/shirts.json", 1.0; "shirts123")]
@@ -28,15 +70,18 @@ mod test_placement {
         let now: DateTime<Local> = Local::now();
         let timestamp = now.format("%Y-%m-%d_%H-%M-%S").to_string();
 
+        let _ = std::fs::create_dir_all("logs");
         let log_filename = format!("logs/place_{}.log", timestamp);
         let log_file = File::create(log_filename);
         let _ = WriteLogger::init(LevelFilter::Info, Config::default(), log_file.unwrap());
 
         let instance = OtherPath::new(instance_path);
         // parse the instance
-        let mut config = SDRConfig::default();
-        config.n_samples = 100;
-        let json_instance = io::read_json_instance(&instance);
+        let config = SDRConfig {
+            n_samples: 100,
+            ..SDRConfig::default()
+        };
+        let json_instance = io::read_json_instance(instance);
         let poly_simpl_config = match config.poly_simpl_tolerance {
             Some(tolerance) => PolySimplConfig::Enabled { tolerance },
             None => PolySimplConfig::Disabled,
@@ -48,14 +93,749 @@ mod test_placement {
         let sol = optimizer.solve();
         log::info!("discreteStrip: {:?}", optimizer.discrete_strip);
         log::info!("target items: {:?}", sol.target_item_qtys);
-        let svg = s_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance.instance, SvgDrawOptions {
+        let svg = s_sdr_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance, &optimizer.mirrored_items, SvgDrawOptions {
             theme: SvgLayoutTheme::default(), // You need to define or load a theme here
             surrogate: false, // Optional: whether to include surrogates in the SVG
             quadtree:false,  // Optional: whether to include quadtree
             haz_prox_grid: false, // Optional: whether to include hazard proximity grid
+            discrete_strip: false, // Optional: whether to include the discretized strip
         });
         let mut file = File::create("solution.svg").expect("Unable to create file");
         file.write_all(svg.to_string().as_bytes()).expect("Unable to write SVG data");
     }
+
+    #[test_case("../assets/baldacci1.json", 50.0; "baldacci1")]
+    #[test_case("../assets/baldacci4.json", 50.0; "baldacci4")]
+    fn test_bin_placement(instance_path: &str, resolution: fsize) {
+        let config = SDRConfig::default();
+        let (optimizer, sol) = solve_complete(parse_instance(instance_path, &config, resolution), config, resolution);
+        assert_eq!(sol.layout_snapshots.len(), optimizer.discrete_bins.len());
+        for (i, layout) in sol.layout_snapshots.iter().enumerate() {
+            let svg = s_sdr_layout_to_svg(layout, &optimizer.instance, &optimizer.mirrored_items, SvgDrawOptions::default());
+            write_test_svg(&svg, &format!("solution_bin_{}.svg", i));
+        }
+    }
+
+    #[test]
+    fn test_nesting_in_holes() {
+        let config = SDRConfig::default();
+        let instance = parse_instance("../assets/gaskets.json", &config, 1.0);
+        assert_eq!(instance.geometry[&0].holes.len(), 1);
+        let (optimizer, sol) = solve_complete(instance, config, 1.0);
+        // all the small squares fit inside the holes of the two frames
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 120.0 + 1e-3, "strip width {}", strip_width);
+        // the holes and the squares nested in them are counted once
+        assert!(sol.usage <= 1.0 && optimizer.report.usage <= 1.0, "usage {}", sol.usage);
+        let material_area = (0..optimizer.instance.instance.items().len())
+            .map(|item_id| optimizer.instance.material_area(item_id) * optimizer.instance.instance.item_qty(item_id) as fsize)
+            .sum::<fsize>();
+        assert!((sol.usage - material_area / sol.layout_snapshots[0].bin.area).abs() < 1e-4);
+
+        let svg = s_sdr_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance, &optimizer.mirrored_items, SvgDrawOptions::default());
+        write_test_svg(&svg, "solution_holes.svg");
+    }
+
+    #[test]
+    fn test_multipolygon_items() {
+        let config = SDRConfig::default();
+        let instance = parse_instance("../assets/kits.json", &config, 1.0);
+        assert_eq!(instance.geometry[&0].components.len(), 2);
+        let (optimizer, sol) = solve_complete(instance, config, 1.0);
+        // the rectangles fit in between the components of the kits
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 80.0 + 1e-3, "strip width {}", strip_width);
+        // only the components are material, not their convex hull
+        assert!(sol.usage <= 1.0 && optimizer.report.usage <= 1.0, "usage {}", sol.usage);
+        let kit = &optimizer.instance.geometry[&0];
+        let components_area = kit.components.iter().map(|component| component.outer.area()).sum::<fsize>();
+        assert!((optimizer.instance.material_area(0) - components_area).abs() < 1e-3);
+        assert!(optimizer.instance.material_area(0) < optimizer.instance.instance.item(0).shape.area());
+
+        let svg = s_sdr_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance, &optimizer.mirrored_items, SvgDrawOptions::default());
+        write_test_svg(&svg, "solution_kits.svg");
+    }
+
+    #[test]
+    fn test_strip_defects() {
+        let config = SDRConfig::default();
+        let instance = parse_instance("../assets/defects.json", &config, 1.0);
+        assert_eq!(instance.strip_defects.len(), 1);
+        let (_, sol) = solve_complete(instance, config, 1.0);
+        // no item covers the defect in [0, 20] x [0, 20]
+        for pi in sol.layout_snapshots[0].placed_items.values() {
+            let bbox = &pi.shape.bbox;
+            assert!(bbox.x_min >= 20.0 - 1e-3 || bbox.y_min >= 20.0 - 1e-3, "item on the defect: {:?}", bbox);
+        }
+    }
+
+    #[test]
+    fn test_defect_beyond_items() {
+        let instance = OtherPath::new("../assets/defects.json");
+        let config = SDRConfig::default();
+        let mut json_instance = io::read_json_instance(instance);
+        let mut json_ext = io::read_json_instance_ext(instance);
+        // two items and a defect far beyond them
+        json_instance.items[0].demand = 2;
+        json_ext.strip.as_mut().unwrap().defects[0] =
+            JsonShape::SimplePolygon(JsonSimplePoly(vec![(200.0, 0.0), (210.0, 0.0), (210.0, 10.0), (200.0, 10.0)]));
+
+        let instance = SdrParse::from_config(&config, true).parse_with_ext(&json_instance, &json_ext, 1.0);
+        let (optimizer, sol) = solve_complete(instance, config, 1.0);
+        // the strip ends after the items, not after the defect
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 20.0 + 1e-3, "strip width {}", strip_width);
+        assert_eq!(optimizer.report.strip_width, Some(strip_width));
+    }
+
+    #[test]
+    fn test_defect_beyond_strip_top() {
+        let instance = OtherPath::new("../assets/defects.json");
+        let config = SDRConfig::default();
+        let mut json_instance = io::read_json_instance(instance);
+        let mut json_ext = io::read_json_instance_ext(instance);
+        // six items and a defect on the top of the strip, most of which lies above it
+        json_instance.items[0].demand = 6;
+        json_ext.strip.as_mut().unwrap().defects[0] =
+            JsonShape::SimplePolygon(JsonSimplePoly(vec![(0.0, 30.0), (20.0, 30.0), (20.0, 60.0), (0.0, 60.0)]));
+
+        let instance = SdrParse::from_config(&config, true).parse_with_ext(&json_instance, &json_ext, 1.0);
+        let (_, sol) = solve_complete(instance, config, 1.0);
+        // the part of the defect above the strip is not folded back onto the lines, the items fill the space below it
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 20.0 + 1e-3, "strip width {}", strip_width);
+        for pi in sol.layout_snapshots[0].placed_items.values() {
+            let bbox = &pi.shape.bbox;
+            assert!(bbox.x_min >= 20.0 - 1e-3 || bbox.y_max <= 30.0 + 1e-3, "item on the defect: {:?}", bbox);
+        }
+    }
+
+    #[test]
+    fn test_strip_quality_zones() {
+        let config = SDRConfig::default();
+        let (_, sol) = solve_complete(parse_instance("../assets/hides.json", &config, 1.0), config, 1.0);
+        // the items of quality 1 stay out of the zone of quality 0 in [0, 20] x [0, 20]
+        for pi in sol.layout_snapshots[0].placed_items.values() {
+            let bbox = &pi.shape.bbox;
+            assert!(bbox.x_min >= 20.0 - 1e-3 || bbox.y_min >= 20.0 - 1e-3, "item in the zone: {:?}", bbox);
+        }
+    }
+
+    #[test]
+    fn test_quality_zone_boundary() {
+        // a zone whose vertical edge runs along the line, and the opposite edge of an item on the same line
+        let mut line = DiscreteLine::new();
+        line.quality_zones.push((0, Interval::new_with_orientation(0.0, 20.0, true)));
+        let mut incoming_line = DiscreteLine::new();
+        incoming_line.add_interval(Interval::new_with_orientation(5.0, 10.0, false));
+        let overlap_checks = Cell::new(0);
+
+        // the edge lies in the zone whatever the orientations, it has to move past the end of the zone
+        assert_eq!(line.check_quality(&incoming_line, &0.0, 1, &overlap_checks), 15.0);
+        assert_eq!(line.check_quality(&incoming_line, &-10.0, 1, &overlap_checks), 0.0);
+        assert_eq!(line.check_quality(&incoming_line, &20.0, 1, &overlap_checks), 0.0);
+        // items of the quality of the zone may lie in it
+        assert_eq!(line.check_quality(&incoming_line, &0.0, 0, &overlap_checks), 0.0);
+        assert_eq!(overlap_checks.get(), 3);
+    }
+
+    #[test_case(RotationSampling::PrincipalAxes; "principal_axes")]
+    #[test_case(RotationSampling::LongestHullEdge; "longest_hull_edge")]
+    fn test_continuous_rotation(rotation_sampling: RotationSampling) {
+        let config = SDRConfig {
+            rotation_sampling,
+            ..SDRConfig::default()
+        };
+        // the planks only fit in the strip when laid flat
+        let (_, sol) = solve_complete(parse_instance("../assets/planks.json", &config, 1.0), config, 1.0);
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 122.0, "strip width {}", strip_width);
+    }
+
+    #[test]
+    fn test_mirrored_items() {
+        let config = SDRConfig::default();
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/wedges.json"));
+        let instance = parse_instance("../assets/wedges.json", &config, 1.0);
+        assert_eq!(instance.orientations(0).len(), 1);
+        assert_eq!(instance.orientations(1).len(), 2);
+        let (optimizer, sol) = solve_complete(instance, config, 1.0);
+        // the second wedge only interlocks with the first one when mirrored
+        assert_eq!(optimizer.mirrored_items.len(), 1);
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= 25.0, "strip width {}", strip_width);
+
+        let svg = s_sdr_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance, &optimizer.mirrored_items, SvgDrawOptions::default());
+        assert!(svg.to_string().contains("scale(-1 1)"));
+        // the shapes of jagua-rs alone know nothing of the mirroring
+        let plain = s_layout_to_svg(&sol.layout_snapshots[0], &optimizer.instance.instance, SvgDrawOptions::default());
+        assert!(!plain.to_string().contains("scale(-1 1)"));
+
+        // in the JSON solution, mirroring the wedge and then transforming it gives the shape which was placed
+        let json_solution = JsonSdrSolution::compose(&sol, &optimizer.instance, &optimizer.mirrored_items, *EPOCH);
+        let placed_items = &json_solution.layouts[0].placed_items;
+        assert_eq!(placed_items.iter().filter(|pi| pi.mirrored).count(), 1);
+        let s_layout = &sol.layout_snapshots[0];
+        for (json_pi, (pik, pi)) in placed_items.iter().zip(s_layout.placed_items.iter()) {
+            assert_eq!(json_pi.mirrored, optimizer.mirrored_items.contains(&(s_layout.id, pik)));
+            let shape = match json_pi.mirrored {
+                true => &optimizer.instance.mirrored_shapes[&pi.item_id],
+                false => optimizer.instance.instance.item(pi.item_id).shape.as_ref(),
+            };
+            let bin_transf = s_layout.bin.pretransform.clone().inverse();
+            let placed = shape.transform_clone(&pi.d_transf.compose().transform(&bin_transf));
+
+            let JsonShape::SimplePolygon(JsonSimplePoly(points)) = &json_instance.items[json_pi.placed_item.index].shape else { unreachable!() };
+            let transformation = &json_pi.placed_item.transformation;
+            let (sin, cos) = transformation.rotation.sin_cos();
+            let (tx, ty) = transformation.translation;
+            for (x, y) in points.iter().map(|&(x, y)| if json_pi.mirrored { (-x, y) } else { (x, y) }) {
+                let (x, y) = (x * cos - y * sin + tx, x * sin + y * cos + ty);
+                assert!(
+                    placed.points.iter().any(|p| (p.0 - x).abs() < 1e-3 && (p.1 - y).abs() < 1e-3),
+                    "({}, {}) is not a corner of {:?}", x, y, placed.points
+                );
+            }
+        }
+        let json = serde_json::to_value(&json_solution).unwrap();
+        assert!(json["Layouts"][0]["PlacedItems"][0].get("Mirrored").is_some());
+        write_test_svg(&svg, "solution_mirrored.svg");
+    }
+
+    #[test]
+    fn test_congruent_orientations() {
+        let config = SDRConfig::default();
+        let mut json_instance = io::read_json_instance(OtherPath::new("../assets/shirts.json"));
+        // the second item is a copy of the first one, the sixth is a rectangle which may be rotated by 180°
+        json_instance.items[1] = json_instance.items[0].clone();
+        let sdr_parser = SdrParse::from_config(&config, true);
+        let instance = sdr_parser.parse(&json_instance, 1.0);
+
+        let n_orientations = (0..instance.instance.items().len()).map(|item_id| instance.rotations[&item_id].len()).sum::<usize>();
+        assert_eq!(instance.items.len() + instance.aliases.len(), n_orientations);
+        for ((_, lines), (_, copied_lines)) in instance.orientations(0).into_iter().zip(instance.orientations(1)) {
+            assert!(std::ptr::eq(lines, copied_lines));
+        }
+        assert_eq!(instance.orientations(1).len(), instance.orientations(0).len());
+        assert_eq!(instance.orientations(5).len(), 1);
+
+        // the rotated rectangle shares the lines it would have been discretized into
+        let rectangle = instance.instance.item(5);
+        let rotated = ItemDiscrete::new(&5, &instance.rotations[&5][1], false);
+        let shared_lines = instance.lines(&rotated).unwrap();
+        let rotated_lines = rectangle.discretize_geometry(1.0, rotated.rotation.into_inner(), &ItemGeometry::default());
+        assert_eq!(shared_lines.len(), rotated_lines.len());
+        for (shared, line) in shared_lines.iter().zip(&rotated_lines) {
+            assert_eq!(shared.occupied.len(), line.occupied.len());
+            for (shared, interval) in shared.occupied.iter().zip(&line.occupied) {
+                assert!((shared.start - interval.start).abs() < 1e-3 && (shared.end - interval.end).abs() < 1e-3);
+            }
+        }
+
+        solve_complete(instance, config, 1.0);
+    }
+
+    #[test]
+    fn test_strip_overlay() {
+        let config = SDRConfig { compaction_sweeps: 0, ..SDRConfig::default() };
+        let (optimizer, sol) = solve_complete(parse_instance("../assets/shirts.json", &config, 1.0), config, 1.0);
+        let s_layout = &sol.layout_snapshots[0];
+        let options = SvgDrawOptions { discrete_strip: true, ..SvgDrawOptions::default() };
+
+        let strip = optimizer.strip_overlay(s_layout.id).unwrap();
+        assert_eq!(strip.discrete_strip.lines.len(), optimizer.discrete_strip.lines.len());
+        // the lines span the strip
+        let bbox = s_layout.bin.bbox();
+        assert!((strip.frame.x_min - bbox.x_min).abs() < 1e-3 && (strip.frame.y_max - bbox.y_max).abs() < 1e-3);
+        let last_line = strip.frame.x_min + (strip.discrete_strip.lines.len() - 1) as fsize * strip.resolution;
+        assert!((last_line - bbox.x_max).abs() < 1e-3, "last line at {}, strip ends at {}", last_line, bbox.x_max);
+        assert!(optimizer.strip_overlay(usize::MAX).is_none());
+
+        let document = s_sdr_layout_to_svg_with_strip(s_layout, &optimizer.instance, &optimizer.mirrored_items, Some(strip), options);
+        let svg = document.to_string();
+        assert!(svg.contains("id=\"discrete_strip\""));
+        for color in ["black", "red", "blue"] {
+            assert!(svg.contains(&format!("stroke=\"{}\"", color)), "no {} intervals", color);
+        }
+        // the overlay is only drawn when asked for and when the strip is passed along
+        let strip = optimizer.strip_overlay(s_layout.id);
+        let plain = s_sdr_layout_to_svg_with_strip(s_layout, &optimizer.instance, &optimizer.mirrored_items, strip, SvgDrawOptions::default());
+        assert!(!plain.to_string().contains("discrete_strip"));
+        assert!(!s_sdr_layout_to_svg(s_layout, &optimizer.instance, &optimizer.mirrored_items, options).to_string().contains("discrete_strip"));
+        write_test_svg(&document, "solution_strip.svg");
+
+        // every bin has its own strip
+        let (optimizer, sol) = solve_complete(parse_instance("../assets/baldacci1.json", &config, 50.0), config, 50.0);
+        for s_layout in &sol.layout_snapshots {
+            let strip = optimizer.strip_overlay(s_layout.id).unwrap();
+            assert_eq!(strip.frame, s_layout.bin.bbox());
+        }
+    }
+
+    #[test_case("../assets/shirts.json"; "shirts")]
+    #[test_case("../assets/swim.json"; "swim")]
+    fn test_minimize_strip(instance_path: &str) {
+        let config = SDRConfig {
+            time_limit: Some(5.0),
+            ..SDRConfig::default()
+        };
+        let resolution = match instance_path {
+            "../assets/swim.json" => 36.0,
+            _ => 1.0,
+        };
+
+        let (_, single_pass) = solve_complete(parse_instance(instance_path, &config, resolution), config, resolution);
+        let single_pass_width = single_pass.layout_snapshots[0].bin.bbox().width();
+
+        let mut optimizer = SDROptimizer::new(parse_instance(instance_path, &config, resolution), config, resolution);
+        let sol = optimizer.minimize_strip();
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= single_pass_width, "strip width {} > {}", strip_width, single_pass_width);
+        for pi in sol.layout_snapshots[0].placed_items.values() {
+            assert!(pi.shape.bbox.x_max <= strip_width + 1e-3, "item outside of the strip: {:?}", pi.shape.bbox);
+        }
+    }
+
+    #[test]
+    fn test_random_search() {
+        let config = SDRConfig {
+            n_samples: 200,
+            ls_frac: 0.2,
+            prng_seed: Some(0),
+            ..SDRConfig::default()
+        };
+        let instance = parse_instance("../assets/shirts.json", &config, 1.0);
+        let (_, single_pass) = solve_complete(instance.clone(), config, 1.0);
+        let single_pass_width = single_pass.layout_snapshots[0].bin.bbox().width();
+
+        let mut widths = vec![];
+        for _ in 0..2 {
+            let mut optimizer = SDROptimizer::new(instance.clone(), config, 1.0);
+            let sol = optimizer.random_search();
+            assert!(sol.is_complete(&optimizer.instance.instance));
+            widths.push(sol.layout_snapshots[0].bin.bbox().width());
+        }
+        // the search starts from the order of a single pass and is deterministic for a given seed
+        assert!(widths[0] <= single_pass_width, "strip width {} > {}", widths[0], single_pass_width);
+        assert_eq!(widths[0], widths[1]);
+    }
+
+    #[test_case("../assets/albano.json", 40.0; "albano")]
+    #[test_case("../assets/trousers.json", 1.0; "trousers")]
+    fn test_portfolio(instance_path: &str, resolution: fsize) {
+        let config = SDRConfig::default();
+        let instance = parse_instance(instance_path, &config, resolution);
+        let n_items = instance.instance.items().len();
+        let (_, single_pass) = solve_complete(instance.clone(), config, resolution);
+        let single_pass_width = single_pass.layout_snapshots[0].bin.bbox().width();
+
+        let mut optimizer = SDROptimizer::new(instance, config, resolution);
+        for ordering in ItemOrdering::ALL {
+            // every ordering is a permutation of the items
+            let mut order = optimizer.item_order(ordering);
+            order.sort();
+            assert_eq!(order, (0..n_items).collect::<Vec<_>>());
+        }
+        let sol = optimizer.solve_portfolio(&ItemOrdering::ALL);
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        // the default ordering is part of the portfolio
+        let strip_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(strip_width <= single_pass_width, "strip width {} > {}", strip_width, single_pass_width);
+    }
+
+    #[test_case("../assets/shirts.json", 1.0; "shirts")]
+    #[test_case("../assets/albano.json", 40.0; "albano")]
+    fn test_genetic_search(instance_path: &str, resolution: fsize) {
+        let config = SDRConfig {
+            brkga: BrkgaConfig {
+                population_size: 20,
+                n_generations: 10,
+                ..BrkgaConfig::default()
+            },
+            ..SDRConfig::default()
+        };
+        let instance = parse_instance(instance_path, &config, resolution);
+        let (optimizer, single_pass) = solve_complete(instance.clone(), config, resolution);
+        let single_pass_width = single_pass.layout_snapshots[0].bin.bbox().width();
+
+        // the order of a single pass survives encoding
+        let n_orientations = (0..instance.instance.items().len()).map(|i| instance.orientations(i).len()).collect::<Vec<_>>();
+        let order = PlacementOrder::new(optimizer.item_order(config.item_ordering));
+        assert_eq!(Chromosome::from_order(&order, &n_orientations).decode(&n_orientations), order);
+
+        let mut widths = vec![];
+        for _ in 0..2 {
+            let mut optimizer = SDROptimizer::new(instance.clone(), config, resolution);
+            let sol = optimizer.genetic_search();
+            assert!(sol.is_complete(&optimizer.instance.instance));
+            widths.push(sol.layout_snapshots[0].bin.bbox().width());
+        }
+        assert!(widths[0] <= single_pass_width, "strip width {} > {}", widths[0], single_pass_width);
+        assert_eq!(widths[0], widths[1]);
+    }
+
+    #[test]
+    fn test_anytime_search() {
+        let config = SDRConfig {
+            n_samples: usize::MAX,
+            ls_frac: 0.5,
+            time_limit: Some(1.0),
+            ..SDRConfig::default()
+        };
+        let instance = parse_instance("../assets/swim.json", &config, 36.0);
+
+        // the deadline ends a search which would never end otherwise, every improvement is reported on the way
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        let (sender, receiver) = mpsc::channel();
+        optimizer.set_solution_listener(move |solution| {
+            sender.send(solution.layout_snapshots[0].bin.bbox().width()).unwrap();
+        });
+        let start_time = Instant::now();
+        let sol = optimizer.random_search();
+        assert!(start_time.elapsed() < Duration::from_secs(5), "search took {:?}", start_time.elapsed());
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        let widths = receiver.try_iter().collect::<Vec<_>>();
+        assert!(!widths.is_empty());
+        assert!(widths.windows(2).all(|w| w[1] < w[0]), "widths {:?}", widths);
+        assert_eq!(*widths.last().unwrap(), sol.layout_snapshots[0].bin.bbox().width());
+
+        // a cancelled search still returns its first solution
+        let config = SDRConfig {
+            time_limit: None,
+            brkga: BrkgaConfig {
+                n_generations: usize::MAX,
+                ..BrkgaConfig::default()
+            },
+            ..config
+        };
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        let token = optimizer.cancellation_token();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            token.cancel();
+        });
+        let sol = optimizer.genetic_search();
+        canceller.join().unwrap();
+        assert!(sol.is_complete(&optimizer.instance.instance));
+
+        // a portfolio and a coarse to fine search cancelled beforehand only try their first ordering,
+        // the coarse to fine search goes straight from the coarsest resolution to the fine one
+        for (coarse_resolutions, passes) in [(None, 1), (Some([144.0, 72.0]), 2)] {
+            let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+            optimizer.cancellation_token().cancel();
+            let sol = match coarse_resolutions {
+                None => optimizer.solve_portfolio(&ItemOrdering::ALL),
+                Some(coarse_resolutions) => optimizer.solve_coarse_to_fine(&coarse_resolutions),
+            };
+            assert!(sol.is_complete(&optimizer.instance.instance));
+            assert_eq!(optimizer.report.passes, passes);
+        }
+
+        // an invalid time limit is ignored rather than a panic
+        let config = SDRConfig { time_limit: Some(-1.0), ..config };
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        let sol = optimizer.solve_portfolio(&ItemOrdering::ALL);
+        assert!(sol.is_complete(&optimizer.instance.instance));
+    }
+
+    #[test]
+    fn test_solve_report() {
+        let config = SDRConfig::default();
+        let instance = parse_instance("../assets/shirts.json", &config, 1.0);
+        let (optimizer, sol) = solve_complete(instance.clone(), config, 1.0);
+        let report = optimizer.report.clone();
+        assert_eq!(report.passes, 1);
+        assert!(report.overlap_checks > 0);
+        assert!(report.lines_scanned > 0);
+        assert_eq!(report.strip_width, Some(sol.layout_snapshots[0].bin.bbox().width()));
+        assert_eq!(report.usage, sol.usage);
+        assert_eq!(report.item_placement_secs.len(), instance.instance.items().len());
+        let item_secs: f64 = report.item_placement_secs.iter().sum();
+        assert!((report.placement_secs - item_secs).abs() < 1e-6);
+
+        // the counters of another optimizer are independent, a repeated solve counts the same
+        let (other, _) = solve_complete(instance.clone(), config, 1.0);
+        assert_eq!(other.report.overlap_checks, report.overlap_checks);
+        assert_eq!(other.report.lines_scanned, report.lines_scanned);
+
+        let config = SDRConfig { n_samples: 5, ..config };
+        let mut optimizer = SDROptimizer::new(instance, config, 1.0);
+        optimizer.random_search();
+        assert_eq!(optimizer.report.passes, 6);
+        assert!(optimizer.report.overlap_checks > report.overlap_checks);
+    }
+
+    #[test]
+    fn test_growing_strip() {
+        let config = SDRConfig::default();
+        let sdr_parser = SdrParse::from_config(&config, true);
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/shirts.json"));
+        let instance = sdr_parser.parse(&json_instance, 1.0);
+
+        // lines are only appended as the items need them
+        let item = instance.instance.item(0);
+        let orientations = instance.orientations(0);
+        let rotation_lines = instance.rotation_lines(0);
+        let height = 40.0;
+        let mut fixed_strip = DiscreteStrip::new(vec![DiscreteLine::new(); 2]);
+        assert!(fixed_strip.try_fit_segments(&rotation_lines, &height, &1.0, &1, &item.id, None).is_empty());
+        let mut growing_strip = DiscreteStrip::growing(Vec::new(), 1000);
+        assert_eq!(growing_strip.try_fit_segments(&rotation_lines, &height, &1.0, &2, &item.id, None).len(), 2);
+        let narrowest = orientations.iter().map(|(_, lines)| lines.len()).min().unwrap();
+        assert!(growing_strip.lines.len() >= narrowest && growing_strip.lines.len() <= 1000);
+
+        // the width of the solution follows the lines which are used
+        let mut optimizer = SDROptimizer::new(instance, config, 1.0);
+        assert_eq!(optimizer.discrete_strip.lines.len(), 1);
+        let sol = optimizer.solve();
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        assert_eq!(sol.layout_snapshots[0].bin.bbox().width(), (optimizer.discrete_strip.lines.len() - 1) as fsize);
+    }
+
+    #[test]
+    fn test_unplaceable_items() {
+        let config = SDRConfig::default();
+        let sdr_parser = SdrParse::from_config(&config, true);
+
+        // the strip grows to fit the long item, only the item which is too tall is left out
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/unplaceable.json"));
+        let mut optimizer = SDROptimizer::new(sdr_parser.parse(&json_instance, 1.0), config, 1.0);
+        let sol = optimizer.solve();
+        assert!(sol.layout_snapshots[0].bin.bbox().width() >= 100.0);
+        assert_eq!(sol.layout_snapshots[0].placed_items.len(), 5);
+        assert_eq!(optimizer.report.unplaced, vec![UnplacedItem { item_id: 1, copies: 2, reason: UnplacedReason::TooTall }]);
+
+        // every search keeps going without the item
+        let sol = optimizer.minimize_strip();
+        assert_eq!(sol.layout_snapshots[0].placed_items.len(), 5);
+        assert_eq!(optimizer.report.unplaced.len(), 1);
+
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/unplaceable_bins.json"));
+        let mut optimizer = SDROptimizer::new(sdr_parser.parse(&json_instance, 1.0), config, 1.0);
+        let sol = optimizer.solve();
+        assert_eq!(sol.layout_snapshots.len(), 1);
+        assert_eq!(sol.layout_snapshots[0].placed_items.len(), 1);
+        let mut unplaced = optimizer.report.unplaced.clone();
+        unplaced.sort_by_key(|unplaced| unplaced.item_id);
+        assert_eq!(
+            unplaced,
+            vec![
+                UnplacedItem { item_id: 0, copies: 1, reason: UnplacedReason::StripExhausted },
+                UnplacedItem { item_id: 1, copies: 1, reason: UnplacedReason::TooTall },
+                UnplacedItem { item_id: 2, copies: 1, reason: UnplacedReason::NoRotationFits },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let config = SDRConfig { validation: Validation::Strict, ..SDRConfig::default() };
+
+        // nested, multipolygon and mirrored items, defects and quality zones are checked on their true geometry
+        for instance_path in ["../assets/shirts.json", "../assets/gaskets.json", "../assets/kits.json", "../assets/defects.json", "../assets/hides.json", "../assets/wedges.json"] {
+            let mut optimizer = SDROptimizer::new(parse_instance(instance_path, &config, 1.0), config, 1.0);
+            assert!(optimizer.try_solve().is_ok(), "{}: {:?}", instance_path, optimizer.report.violations);
+            assert!(optimizer.report.violations.is_empty());
+
+            // a copy placed over the first item overlaps it
+            let placed_item = optimizer.problem.get_layout(STRIP_LAYOUT_IDX).placed_items.values().next().unwrap();
+            let mut placing_option = PlacingOption::from_placed_item(STRIP_LAYOUT_IDX, placed_item);
+            let (x, y) = placing_option.d_transf.translation();
+            placing_option.d_transf = DTransformation::new(placing_option.d_transf.rotation(), (x + 2.0, y));
+            optimizer.problem.place_item(placing_option);
+            let violations = optimizer.validate();
+            let overlap = ViolationKind::Overlap { other_item_id: placing_option.item_id };
+            assert!(
+                violations.iter().any(|violation| violation.kind == overlap && violation.penetration_depth > 1.0),
+                "{}: {:?}",
+                instance_path,
+                violations
+            );
+        }
+
+        // the coarse resolution lets items overlap between the discrete lines
+        let instance = parse_instance("../assets/swim.json", &config, 36.0);
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        assert!(matches!(optimizer.try_solve(), Err(SdrError::InfeasibleSolution(violations)) if !violations.is_empty()));
+        // the searches fail the same way as a single pass
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        assert!(matches!(optimizer.try_solve_portfolio(&ItemOrdering::ALL), Err(SdrError::InfeasibleSolution(_))));
+        let mut optimizer = SDROptimizer::new(instance.clone(), SDRConfig { n_samples: 5, ..config }, 36.0);
+        assert!(matches!(optimizer.try_random_search(), Err(SdrError::InfeasibleSolution(_))));
+        let config = SDRConfig { validation: Validation::Report, ..config };
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 36.0);
+        optimizer.solve();
+        assert!(optimizer.report.violations.iter().all(|violation| violation.penetration_depth > config.validation_tolerance));
+        assert!(!optimizer.report.violations.is_empty());
+        // by default, the solution is not validated
+        let mut optimizer = SDROptimizer::new(instance, SDRConfig::default(), 36.0);
+        assert!(optimizer.try_solve().is_ok());
+        assert!(optimizer.report.violations.is_empty());
+    }
+
+    #[test_case("../assets/albano.json", 40.0; "albano")]
+    #[test_case("../assets/wedges.json", 1.0; "wedges")]
+    fn test_compaction(instance_path: &str, resolution: fsize) {
+        let config = SDRConfig { validation: Validation::Strict, ..SDRConfig::default() };
+        let instance = parse_instance(instance_path, &config, resolution);
+
+        let (optimizer, discrete_sol) = solve_complete(instance.clone(), config, resolution);
+        let discrete_width = discrete_sol.layout_snapshots[0].bin.bbox().width();
+        let n_mirrored = optimizer.mirrored_items.len();
+        assert_eq!(optimizer.report.compaction_secs, 0.0);
+
+        let config = SDRConfig { compaction_sweeps: 3, ..config };
+        let mut optimizer = SDROptimizer::new(instance, config, resolution);
+        let sol = optimizer.try_solve().unwrap();
+        // the items slide into the slivers left by the resolution without colliding
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        assert!(optimizer.report.violations.is_empty());
+        let width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(width < discrete_width, "compacted {} discrete {}", width, discrete_width);
+        assert_eq!(optimizer.report.strip_width, Some(width));
+        assert_eq!(optimizer.mirrored_items.len(), n_mirrored);
+    }
+
+    #[test]
+    fn test_coarse_to_fine() {
+        let config = SDRConfig::default();
+        let instance = parse_instance("../assets/trousers.json", &config, 0.5);
+        let (_, sol) = solve_complete(instance.clone(), config, 0.5);
+        let width = sol.layout_snapshots[0].bin.bbox().width();
+
+        let mut optimizer = SDROptimizer::new(instance.clone(), config, 0.5);
+        optimizer.solve_portfolio(&ItemOrdering::ALL);
+        let portfolio_lines_scanned = optimizer.report.lines_scanned;
+
+        let mut optimizer = SDROptimizer::new(instance, config, 0.5);
+        // the resolutions which are not coarser are ignored
+        let sol = optimizer.solve_coarse_to_fine(&[1.0, 0.5, 4.0, 2.0, 0.25]);
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        let coarse_to_fine_width = sol.layout_snapshots[0].bin.bbox().width();
+        assert!(coarse_to_fine_width <= width, "coarse to fine {} solve {}", coarse_to_fine_width, width);
+        // 8 orderings at 4.0, 4 at 2.0, 2 at 1.0 and 1 at 0.5
+        assert_eq!(optimizer.report.passes, 8 + 4 + 2 + 1);
+        // the finer passes start from the coarse positions, they scan far fewer lines than the orderings on the fine grid
+        assert!(
+            optimizer.report.lines_scanned * 2 < portfolio_lines_scanned,
+            "coarse to fine scanned {} lines, the portfolio {}",
+            optimizer.report.lines_scanned,
+            portfolio_lines_scanned
+        );
+        // the instance is discretized again at every coarse resolution
+        assert!(optimizer.report.discretization_secs > optimizer.instance.discretization_time.as_secs_f64());
+        assert_eq!(optimizer.discrete_strip.lines.len() - 1, (coarse_to_fine_width / 0.5).round() as usize);
+    }
+
+    #[test_case("../assets/shirts.json"; "shirts")]
+    #[test_case("../assets/albano.json"; "albano")]
+    #[test_case("../assets/gaskets.json"; "gaskets")]
+    fn test_resolution_advisor(instance_path: &str) {
+        let instance = OtherPath::new(instance_path);
+        let config = SDRConfig::default();
+        let json_instance = io::read_json_instance(instance);
+        let json_ext = io::read_json_instance_ext(instance);
+        let sdr_parser = SdrParse::from_config(&config, true);
+
+        let (sdr_instance, advice) = sdr_parser.parse_advised(&json_instance, &json_ext, &config, ResolutionTarget::Lines(256));
+        assert!(advice.resolution <= advice.min_item_width / 2.0 && advice.resolution <= advice.median_edge_length);
+        assert!(advice.lines <= 257);
+        assert!(advice.over_approximation > 0.0);
+        let (_, finer) = sdr_parser.parse_advised(&json_instance, &json_ext, &config, ResolutionTarget::Lines(1024));
+        assert!(finer.resolution < advice.resolution);
+        assert!(finer.over_approximation < advice.over_approximation);
+        // a single line is capped by the geometry
+        let (_, capped) = sdr_parser.parse_advised(&json_instance, &json_ext, &config, ResolutionTarget::Lines(1));
+        assert_eq!(capped.resolution, (capped.min_item_width / 2.0).min(capped.median_edge_length));
+
+        // the instance is discretized at the advised resolution
+        let discretized = sdr_parser.parse_with_ext(&json_instance, &json_ext, advice.resolution);
+        for (item_discrete, lines) in &discretized.items {
+            assert_eq!(sdr_instance.items[item_discrete].len(), lines.len());
+        }
+        solve_complete(sdr_instance, config, advice.resolution);
+
+        let (_, timed) = sdr_parser.parse_advised(&json_instance, &json_ext, &config, ResolutionTarget::TimeBudget(Duration::from_millis(100)));
+        assert!(timed.expected_secs.is_some());
+        assert!(timed.lines <= 4097);
+    }
+
+    #[test_case("../assets/shirts.json", 1.5, 2.0; "shirts")]
+    #[test_case("../assets/gaskets.json", 1.5, 0.0; "gaskets")]
+    #[test_case("../assets/defects.json", 1.5, 2.0; "defects")]
+    fn test_spacing(instance_path: &str, spacing: fsize, margin: fsize) {
+        let resolution = 1.0;
+        let config = SDRConfig { item_spacing: spacing, edge_margin: margin, compaction_sweeps: 3, validation: Validation::Strict, ..SDRConfig::default() };
+
+        let mut optimizer = SDROptimizer::new(parse_instance(instance_path, &config, resolution), config, resolution);
+        let sol = optimizer.try_solve().unwrap();
+        assert!(sol.is_complete(&optimizer.instance.instance));
+        let layout = &sol.layout_snapshots[0];
+        let strip = layout.bin.bbox();
+        // the snapshot holds the original shapes, not the inflated ones
+        let shapes = layout.placed_items.values().map(|placed_item| placed_item.shape.as_ref()).collect::<Vec<_>>();
+        for placed_item in layout.placed_items.values() {
+            let original = optimizer.instance.instance.item(placed_item.item_id).shape.area();
+            assert!((placed_item.shape.area() - original).abs() <= original * 1e-3);
+        }
+        for shape in &shapes {
+            assert!(shape.bbox().x_min >= strip.x_min + margin - 1e-3);
+            assert!(shape.bbox().y_min >= strip.y_min + margin - 1e-3);
+            assert!(shape.bbox().y_max <= strip.y_max - margin + 1e-3);
+        }
+        for (i, a) in shapes.iter().enumerate() {
+            for b in &shapes[i + 1..] {
+                let gap = a.points.iter().map(|point| b.separation_distance(point).1)
+                    .chain(b.points.iter().map(|point| a.separation_distance(point).1))
+                    .fold(fsize::INFINITY, fsize::min);
+                assert!(gap >= spacing - 1e-3, "items {} apart", gap);
+            }
+        }
+
+        let plain_config = SDRConfig::default();
+        let (_, plain) = solve_complete(parse_instance(instance_path, &plain_config, resolution), plain_config, resolution);
+        let plain_width = plain.layout_snapshots[0].bin.bbox().width();
+        assert!(strip.width() > plain_width);
+    }
+
+    #[test]
+    fn test_discretization_cache() {
+        let instance = OtherPath::new("../assets/shirts.json");
+        let config = SDRConfig { rotation_sampling: RotationSampling::Uniform(8), ..SDRConfig::default() };
+        let json_instance = io::read_json_instance(instance);
+        let cache_dir = std::env::temp_dir().join(format!("sdr_cache_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let cached_parser = SdrParse::from_config(&config, true).with_discretization_cache(&cache_dir);
+        let cached_files = || std::fs::read_dir(&cache_dir).unwrap().count();
+        let as_json = |lines: &Vec<DiscreteLine>| serde_json::to_string(lines).unwrap();
+
+        let uncached = SdrParse::from_config(&config, true).parse(&json_instance, 1.0);
+        let stored = cached_parser.parse(&json_instance, 1.0);
+        let n_files = cached_files();
+        assert!(n_files > 0 && n_files <= stored.items.len());
+        let loaded = cached_parser.parse(&json_instance, 1.0);
+        assert_eq!(cached_files(), n_files);
+        for (item_discrete, lines) in &uncached.items {
+            assert_eq!(as_json(&stored.items[item_discrete]), as_json(lines));
+            assert_eq!(as_json(&loaded.items[item_discrete]), as_json(lines));
+        }
+
+        // an unreadable file is discretized again and overwritten
+        let corrupted = std::fs::read_dir(&cache_dir).unwrap().next().unwrap().unwrap().path();
+        std::fs::write(&corrupted, "corrupted").unwrap();
+        let reloaded = cached_parser.parse(&json_instance, 1.0);
+        for (item_discrete, lines) in &uncached.items {
+            assert_eq!(as_json(&reloaded.items[item_discrete]), as_json(lines));
+        }
+        assert!(bincode::deserialize::<Vec<DiscreteLine>>(&std::fs::read(&corrupted).unwrap()).is_ok());
+
+        // another resolution or spacing gets its own files, also when discretizing again
+        let _ = stored.rediscretize(2.0);
+        let spaced = SDRConfig { item_spacing: 1.0, ..config };
+        let _ = SdrParse::from_config(&spaced, true).with_discretization_cache(&cache_dir).parse(&json_instance, 1.0);
+        assert_eq!(cached_files(), 3 * n_files);
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
     
 }
@@ -1,14 +1,20 @@
 #[cfg(test)]
 mod test_placement {
     use jagua_rs::entities::instances::instance_generic::InstanceGeneric;
-    use jagua_rs::fsize;
+    use jagua_rs::{fsize, PI};
     use jagua_rs::geometry::geo_traits::Transformable;
+    use jagua_rs::io::json_instance::{JsonShape, JsonSimplePoly};
     use sdr::io::svg_export::simple_polygon_data;
+    use sdr::io::svg_util::Color;
     use sdr::sdr_config::SDRConfig;
+    use sdr::sdr_error::SdrError;
+    use sdr::sdr_optimizer::SDROptimizer;
     use sdr::sdr_parse::SdrParse;
     use test_case::test_case;
     use sdr::discrete_item::Discretizable;
     use sdr::io;
+    use sdr::io::json_instance_ext::JsonInstanceExt;
+    use sdr::resolution_advisor::ResolutionTarget;
     use svg::node::element::{Path, Line};
     use svg::Document;
     use std::fs::File;
@@ -31,15 +37,18 @@ mod test_placement {
         let now: DateTime<Local> = Local::now();
         let timestamp = now.format("%Y-%m-%d_%H-%M-%S").to_string();
 
+        let _ = std::fs::create_dir_all("logs");
         let log_filename = format!("logs/log_{}.log", timestamp);
         let log_file = File::create(log_filename);
         let _ = WriteLogger::init(LevelFilter::Info, Config::default(), log_file.unwrap());
         
         let instance = OtherPath::new(instance_path);
         // parse the instance
-        let mut config = SDRConfig::default();
-        config.n_samples = 100;
-        let json_instance = io::read_json_instance(&instance);
+        let config = SDRConfig {
+            n_samples: 100,
+            ..SDRConfig::default()
+        };
+        let json_instance = io::read_json_instance(instance);
         let poly_simpl_config = match config.poly_simpl_tolerance {
             Some(tolerance) => PolySimplConfig::Enabled { tolerance },
             None => PolySimplConfig::Disabled,
@@ -49,9 +58,9 @@ mod test_placement {
         log::info!("instance: {:?}", instance.items);
         for (i, item) in instance.instance.items().iter().enumerate() {
             let moved_shape = item.0.shape.transform_clone(&item.0.move_to_first_quadrant(0.0));
-            let rotated_shape = item.0.shape.transform_clone(&item.0.move_to_first_quadrant(3.1415927));
+            let rotated_shape = item.0.shape.transform_clone(&item.0.move_to_first_quadrant(PI));
             let discretized_shape = item.0.discretize_shape(resolution, 0.0);
-            let rotatedd_shape = item.0.discretize_shape(resolution, 3.1415927);
+            let rotatedd_shape = item.0.discretize_shape(resolution, PI);
             let polygon_data = simple_polygon_data(&moved_shape);
             let rotated_data = simple_polygon_data(&rotated_shape);
             // Obtain the bounding box from the polygon's shape
@@ -149,4 +158,77 @@ mod test_placement {
         }
 
     }
+
+    #[test]
+    fn test_first_quadrant_rotated() {
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/planks.json"));
+        let sdr_parser = SdrParse::from_config(&SDRConfig::default(), true);
+        let instance = sdr_parser.parse(&json_instance, 1.0);
+        let item = &instance.instance.item(0);
+        for rotation in [0.0, PI / 2.0, 1.0, PI] {
+            let moved_shape = item.shape.transform_clone(&item.move_to_first_quadrant(rotation));
+            // the shape lies against both axes, below the x axis
+            assert!(moved_shape.bbox.x_min.abs() < 1e-3, "x_min {} at {}", moved_shape.bbox.x_min, rotation);
+            assert!(moved_shape.bbox.y_max.abs() < 1e-3, "y_max {} at {}", moved_shape.bbox.y_max, rotation);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let missing = OtherPath::new("../assets/missing.json");
+        match io::try_read_json_instance(missing) {
+            Err(SdrError::Io { path, .. }) => assert_eq!(path, missing),
+            _ => panic!("a missing file is not reported"),
+        }
+        let not_an_instance = OtherPath::new("../assets/config_lbf.json");
+        assert!(matches!(io::try_read_json_instance(not_an_instance), Err(SdrError::Json { .. })));
+
+        let config = SDRConfig::default();
+        let sdr_parser = SdrParse::from_config(&config, true);
+        let json_instance = io::read_json_instance(OtherPath::new("../assets/shirts.json"));
+        assert!(matches!(sdr_parser.try_parse(&json_instance, 0.0), Err(SdrError::InvalidResolution(_))));
+
+        // a polygon without area names its item
+        let mut degenerate = json_instance.clone();
+        degenerate.items[3].shape = JsonShape::SimplePolygon(JsonSimplePoly(vec![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]));
+        match sdr_parser.try_parse(&degenerate, 1.0) {
+            Err(SdrError::InvalidItem { item_id, reason }) => assert_eq!(item_id, 3, "{}", reason),
+            _ => panic!("a degenerate item is not reported"),
+        }
+
+        // an instance without a strip nor bins has nothing to pack into
+        let mut containerless = json_instance.clone();
+        containerless.strip = None;
+        assert!(matches!(sdr_parser.try_parse(&containerless, 1.0), Err(SdrError::InvalidInstance(_))));
+
+        // an item taller than the strip in every orientation is only reported by the optimizer
+        let mut oversized = json_instance.clone();
+        let strip_height = oversized.strip.as_ref().unwrap().height;
+        oversized.items[5].shape = JsonShape::Rectangle { width: strip_height * 2.0, height: strip_height * 2.0 };
+        let instance = sdr_parser.try_parse(&oversized, 1.0).unwrap();
+        match SDROptimizer::try_new(instance, config, 1.0) {
+            Err(SdrError::ItemDoesNotFit { item_id, reason }) => assert_eq!(item_id, 5, "{}", reason),
+            _ => panic!("an oversized item is not reported"),
+        }
+        let instance = sdr_parser.try_parse(&json_instance, 1.0).unwrap();
+        assert!(SDROptimizer::try_new(instance, config, 1.0).is_ok());
+
+        // no resolution is advised without items
+        let mut empty = json_instance.clone();
+        empty.items.clear();
+        let target = ResolutionTarget::Lines(256);
+        assert!(matches!(sdr_parser.try_parse_advised(&empty, &JsonInstanceExt::default(), &config, target), Err(SdrError::InvalidInstance(_))));
+
+        // a time limit which is no duration is rejected before any search
+        for time_limit in [-1.0, f32::NAN, f32::INFINITY] {
+            let instance = sdr_parser.try_parse(&json_instance, 1.0).unwrap();
+            let config = SDRConfig { time_limit: Some(time_limit), ..config };
+            assert!(matches!(SDROptimizer::try_new(instance, config, 1.0), Err(SdrError::InvalidTimeLimit(_))));
+        }
+
+        assert!(matches!("#12345G".parse::<Color>(), Err(SdrError::InvalidColor(_))));
+        assert!(matches!("#fff".parse::<Color>(), Err(SdrError::InvalidColor(_))));
+        assert_eq!("#1A2B3C".parse::<Color>().unwrap(), "1a2b3c".parse::<Color>().unwrap());
+        assert_eq!("#1A2B3C".parse::<Color>().unwrap(), Color::from_hex(0x1A2B3C));
+    }
 }